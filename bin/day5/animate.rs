@@ -0,0 +1,276 @@
+use std::{
+    io::{self, IsTerminal, Read, Write},
+    process::Command,
+    sync::mpsc::{self, Receiver, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    crane::{Crane, Mode},
+    history::History,
+    invalid_data,
+    operation::Operation,
+    render_stacks_with, top_of_stacks,
+};
+
+const HIGHLIGHT: &str = "\x1b[1;33m";
+const RESET: &str = "\x1b[0m";
+const CLEAR: &str = "\x1b[2J\x1b[H";
+const CTRL_C: u8 = 0x03;
+
+// Puts the terminal in non-canonical mode while animating so single key
+// presses are delivered without waiting for enter, and restores it on drop.
+// Ctrl-C arrives as a key instead of a signal, which would kill the process
+// without restoring the terminal.
+struct RawTerminal {
+    enabled: bool,
+}
+
+impl RawTerminal {
+    fn new() -> Self {
+        let enabled = io::stdin().is_terminal()
+            && Command::new("stty")
+                .args(["-icanon", "-echo", "-isig"])
+                .status()
+                .is_ok_and(|s| s.success());
+        Self { enabled }
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        if self.enabled {
+            let _ = Command::new("stty")
+                .args(["icanon", "echo", "isig"])
+                .status();
+        }
+    }
+}
+
+fn spawn_key_reader() -> Receiver<u8> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0u8; 1];
+        let mut stdin = io::stdin().lock();
+        while let Ok(1) = stdin.read(&mut buf) {
+            if tx.send(buf[0]).is_err() {
+                break;
+            }
+        }
+    });
+    rx
+}
+
+// Highlights the crates the operation has just moved
+fn render_moved(stacks: &[Vec<char>], labels: &[String], op: Option<&Operation>) -> String {
+    render_stacks_with(stacks, labels, |i, h, c| {
+        let stack = i as u32 + 1;
+        let moved = match op {
+            Some(Operation::Move(instr)) => {
+                stack == instr.to && h + instr.amount as usize >= stacks[i].len()
+            }
+            Some(Operation::Swap(a, b)) => stack == *a || stack == *b,
+            Some(Operation::Reverse(s)) => stack == *s,
+            None => false,
+        };
+
+        if moved {
+            format!("{}[{}]{}", HIGHLIGHT, c, RESET)
+        } else {
+            format!("[{}]", c)
+        }
+    })
+}
+
+enum Wait {
+    Forward,
+    Back,
+    Quit,
+}
+
+struct Player {
+    keys: Receiver<u8>,
+    delay: Duration,
+    paused: bool,
+    // Whether the keys ran out, so nobody can step back or quit any more
+    closed: bool,
+}
+
+impl Player {
+    // Waits for the next step to be due, handling key presses in the meantime.
+    fn wait(&mut self) -> Wait {
+        loop {
+            let key = if self.paused {
+                self.keys.recv().map_err(|_| RecvTimeoutError::Disconnected)
+            } else {
+                self.keys.recv_timeout(self.delay)
+            };
+
+            match key {
+                Ok(b' ') => self.paused = !self.paused,
+                Ok(b'n') if self.paused => return Wait::Forward,
+                Ok(b'b') if self.paused => return Wait::Back,
+                Ok(b'+') => self.delay = (self.delay / 2).max(Duration::from_millis(10)),
+                Ok(b'-') => self.delay = (self.delay * 2).min(Duration::from_secs(5)),
+                Ok(b'q' | CTRL_C) => return Wait::Quit,
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) => return Wait::Forward,
+                Err(RecvTimeoutError::Disconnected) => {
+                    // No more input, keep playing at the current speed
+                    self.closed = true;
+                    self.paused = false;
+                    thread::sleep(self.delay);
+                    return Wait::Forward;
+                }
+            }
+        }
+    }
+}
+
+fn draw(
+    out: &mut impl Write,
+    name: &str,
+    labels: &[String],
+    operations: &[Operation],
+    history: &History,
+    player: &Player,
+) -> io::Result<()> {
+    let step = history.position();
+    let stacks = history.stacks();
+    let applied = history.last_applied();
+
+    write!(out, "{}", CLEAR)?;
+    writeln!(out, "{} - step {}/{}", name, step, operations.len())?;
+    match (step.checked_sub(1).map(|i| &operations[i]), applied) {
+        (None, _) => writeln!(out, "start\n")?,
+        (Some(op), Some(applied)) if op == applied => writeln!(out, "{}\n", op)?,
+        (Some(op), Some(applied)) => writeln!(out, "{} (executed as {})\n", op, applied)?,
+        (Some(op), None) => writeln!(out, "{} (skipped)\n", op)?,
+    }
+    writeln!(out, "{}\n", render_moved(stacks, labels, applied))?;
+    writeln!(out, "Top: {}", top_of_stacks(stacks))?;
+    writeln!(
+        out,
+        "Delay: {}ms{}  [space] pause  [n/b] step  [+/-] speed  [q] quit",
+        player.delay.as_millis(),
+        if player.paused { " (paused)" } else { "" }
+    )?;
+    out.flush()
+}
+
+pub fn run(
+    stacks: Vec<Vec<char>>,
+    labels: &[String],
+    operations: &[Operation],
+    crane: &dyn Crane,
+    mode: Mode,
+    delay_ms: u64,
+) -> io::Result<()> {
+    let name = crane.name();
+    let mut history = History::new(crane, operations, stacks, mode);
+    let _terminal = RawTerminal::new();
+    let mut player = Player {
+        keys: spawn_key_reader(),
+        delay: Duration::from_millis(delay_ms),
+        paused: false,
+        closed: false,
+    };
+    let mut out = io::stdout();
+
+    draw(&mut out, &name, labels, operations, &history, &player)?;
+    loop {
+        if history.position() == operations.len() {
+            if player.closed {
+                return Ok(());
+            }
+            // Hold the final state until stepping back or quitting
+            player.paused = true;
+        }
+
+        match player.wait() {
+            Wait::Forward => history.forward().map_err(invalid_data)?,
+            Wait::Back => history.back().map_err(invalid_data)?,
+            Wait::Quit => return Ok(()),
+        };
+
+        draw(&mut out, &name, labels, operations, &history, &player)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crane::CrateMover9001, example, parse_labels};
+
+    #[test]
+    fn test_render_moved_highlights_top_of_target() {
+        let stacks = vec![vec!['Z'], vec!['M', 'C', 'D'], vec!['P']];
+        let op: Operation = "move 2 from 1 to 2".parse().unwrap();
+
+        let labels = parse_labels(" 1   2   3 ").unwrap();
+        let frame = render_moved(&stacks, &labels, Some(&op));
+        let rows: Vec<&str> = frame.lines().collect();
+        assert_eq!(rows[0], format!("    {}[D]{}    ", HIGHLIGHT, RESET));
+        assert_eq!(rows[1], format!("    {}[C]{}    ", HIGHLIGHT, RESET));
+        assert_eq!(rows[2], "[Z] [M] [P]");
+    }
+
+    #[test]
+    fn test_render_moved_without_instruction() {
+        let (stacks, _) = example();
+        let labels = parse_labels(" 1   2   3 ").unwrap();
+        assert!(!render_moved(&stacks, &labels, None).contains(HIGHLIGHT));
+    }
+
+    #[test]
+    fn test_wait_quits_on_ctrl_c() {
+        let (tx, keys) = mpsc::channel();
+        let mut player = Player {
+            keys,
+            delay: Duration::from_secs(5),
+            paused: true,
+            closed: false,
+        };
+        tx.send(b'x').unwrap();
+        tx.send(CTRL_C).unwrap();
+        assert!(matches!(player.wait(), Wait::Quit));
+    }
+
+    #[test]
+    fn test_draw_shows_executed_operation() {
+        let operations: Vec<Operation> = vec![
+            "move 3 from 1 to 2".parse().unwrap(),
+            "move 1 from 1 to 9".parse().unwrap(),
+        ];
+        let mut history = History::new(
+            &CrateMover9001,
+            &operations,
+            vec![vec!['A'], vec!['B']],
+            Mode::Lenient,
+        );
+        let player = Player {
+            keys: mpsc::channel().1,
+            delay: Duration::from_millis(100),
+            paused: false,
+            closed: false,
+        };
+        let labels = parse_labels(" 1   2 ").unwrap();
+        let frame = |history: &History| {
+            let mut out = vec![];
+            draw(&mut out, "test", &labels, &operations, history, &player).unwrap();
+            String::from_utf8(out).unwrap()
+        };
+
+        // Only the one crate there was is moved and highlighted
+        history.forward().unwrap();
+        let drawn = frame(&history);
+        assert!(drawn.contains("move 3 from 1 to 2 (executed as move 1 from 1 to 2)"));
+        assert!(drawn.contains(&format!("    {}[A]{}\n    [B]", HIGHLIGHT, RESET)));
+
+        history.forward().unwrap();
+        let drawn = frame(&history);
+        assert!(drawn.contains("move 1 from 1 to 9 (skipped)"));
+        assert!(!drawn.contains(HIGHLIGHT));
+    }
+}
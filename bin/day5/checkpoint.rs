@@ -0,0 +1,391 @@
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    crane::{execute, Crane, Mode},
+    invalid_data,
+    operation::Operation,
+};
+
+// Snapshots of the stacks taken every `interval` operations, so the state after
+// any operation can be found by replaying at most `interval - 1` operations.
+pub struct Checkpoints<'a> {
+    crane: &'a dyn Crane,
+    operations: &'a [Operation],
+    mode: Mode,
+    interval: usize,
+    // `states[i]` is the state after `i * interval` operations
+    states: Vec<Vec<Vec<char>>>,
+}
+
+impl<'a> Checkpoints<'a> {
+    pub fn build(
+        crane: &'a dyn Crane,
+        operations: &'a [Operation],
+        start: Vec<Vec<char>>,
+        mode: Mode,
+        interval: usize,
+    ) -> Result<Self, String> {
+        if interval == 0 {
+            return Err(String::from("Checkpoint interval must be at least 1"));
+        }
+
+        let mut stacks = start.clone();
+        let mut states = vec![start];
+        for (i, op) in operations.iter().enumerate() {
+            execute(crane, &mut stacks, i + 1, op, mode)?;
+            if (i + 1) % interval == 0 {
+                states.push(stacks.clone());
+            }
+        }
+
+        Ok(Self {
+            crane,
+            operations,
+            mode,
+            interval,
+            states,
+        })
+    }
+
+    // The stacks after the first `step` operations
+    pub fn state_at(&self, step: usize) -> Result<Vec<Vec<char>>, String> {
+        if step > self.operations.len() {
+            return Err(format!(
+                "Step {} is past the last of {} operations",
+                step,
+                self.operations.len()
+            ));
+        }
+
+        let checkpoint = step / self.interval;
+        let mut stacks = self.states[checkpoint].clone();
+        for i in checkpoint * self.interval..step {
+            execute(
+                self.crane,
+                &mut stacks,
+                i + 1,
+                &self.operations[i],
+                self.mode,
+            )?;
+        }
+        Ok(stacks)
+    }
+
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    // File format, one line per checkpoint after a header line:
+    //
+    //   checkpoints <interval> <number of operations> <fingerprint> <mode> <crane name>
+    //   <step> <length>:<crates> <length>:<crates> ...
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(
+            out,
+            "checkpoints {} {} {:016x} {} {}",
+            self.interval,
+            self.operations.len(),
+            fingerprint(self.operations),
+            mode_name(self.mode),
+            self.crane.name()
+        )?;
+        for (i, stacks) in self.states.iter().enumerate() {
+            write!(out, "{}", i * self.interval)?;
+            for s in stacks {
+                write!(out, " {}:{}", s.len(), s.iter().collect::<String>())?;
+            }
+            writeln!(out)?;
+        }
+        out.flush()
+    }
+
+    // Loads checkpoints saved for the same crane, mode, start stacks and
+    // operations, anything else would give wrong states
+    pub fn load(
+        path: &Path,
+        crane: &'a dyn Crane,
+        operations: &'a [Operation],
+        start: &[Vec<char>],
+        mode: Mode,
+    ) -> io::Result<Self> {
+        let file = File::open(path)?;
+        let mut lines = BufReader::new(file).lines();
+
+        let header = lines.next().transpose()?.unwrap_or_default();
+        let expected = format!(
+            "{} operations with fingerprint {:016x}, {} mode and the {}",
+            operations.len(),
+            fingerprint(operations),
+            mode_name(mode),
+            crane.name()
+        );
+        let interval = match header.splitn(6, ' ').collect::<Vec<&str>>().as_slice() {
+            ["checkpoints", interval, count, fingerprint_field, mode_field, crane_field]
+                if count.parse() == Ok(operations.len())
+                    && u64::from_str_radix(fingerprint_field, 16)
+                        == Ok(fingerprint(operations))
+                    && *mode_field == mode_name(mode)
+                    && *crane_field == crane.name() =>
+            {
+                interval
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|i| *i > 0)
+                    .ok_or_else(|| {
+                        invalid_data(format!("Invalid checkpoint interval '{}'", interval))
+                    })?
+            }
+            _ => {
+                return Err(invalid_data(format!(
+                    "Checkpoint header '{}' does not match {}",
+                    header, expected
+                )))
+            }
+        };
+
+        let mut states = vec![];
+        for (i, line) in lines.enumerate() {
+            let line = line?;
+            let (step, stacks) = parse_checkpoint(&line, start.len()).map_err(invalid_data)?;
+            if step != i * interval {
+                return Err(invalid_data(format!(
+                    "Expected checkpoint for step {}, found {}",
+                    i * interval,
+                    step
+                )));
+            }
+            states.push(stacks);
+        }
+
+        if states.len() != operations.len() / interval + 1 {
+            return Err(invalid_data(format!(
+                "Expected {} checkpoints, found {}",
+                operations.len() / interval + 1,
+                states.len()
+            )));
+        }
+        if states[0] != start {
+            return Err(invalid_data(format!(
+                "Checkpoints in {} start from other stacks than the drawing",
+                path.display()
+            )));
+        }
+
+        Ok(Self {
+            crane,
+            operations,
+            mode,
+            interval,
+            states,
+        })
+    }
+}
+
+// FNV-1a hash of the operations as written in the instructions, one per line.
+// Unlike `DefaultHasher` it stays the same across Rust releases, so saved
+// checkpoints remain valid.
+fn fingerprint(operations: &[Operation]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for op in operations {
+        for byte in format!("{}\n", op).bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+    hash
+}
+
+fn mode_name(mode: Mode) -> &'static str {
+    match mode {
+        Mode::Strict => "strict",
+        Mode::Lenient => "lenient",
+    }
+}
+
+// Parses a checkpoint line, which has to hold `stack_count` stacks
+fn parse_checkpoint(line: &str, stack_count: usize) -> Result<(usize, Vec<Vec<char>>), String> {
+    let invalid = || format!("Malformed checkpoint '{}'", line);
+
+    let (step, mut rest) = line.split_once(' ').unwrap_or((line, ""));
+    let step = step.parse::<usize>().map_err(|_| invalid())?;
+
+    let mut stacks = vec![];
+    while !rest.is_empty() {
+        let (len, crates) = rest.split_once(':').ok_or_else(invalid)?;
+        let len = len.parse::<usize>().map_err(|_| invalid())?;
+
+        let mut chars = crates.chars();
+        let stack: Vec<char> = chars.by_ref().take(len).collect();
+        if stack.len() != len {
+            return Err(invalid());
+        }
+        stacks.push(stack);
+
+        rest = chars.as_str();
+        rest = rest.strip_prefix(' ').unwrap_or(rest);
+    }
+
+    if stacks.len() != stack_count {
+        return Err(format!(
+            "Checkpoint '{}' has {} stacks instead of {}",
+            line,
+            stacks.len(),
+            stack_count
+        ));
+    }
+
+    Ok((step, stacks))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crane::{execute_all, CrateMover9000, CrateMover9001};
+
+    // The puzzle example with a blank crate, which has to survive saving, and
+    // a swap and a reverse
+    fn example() -> (Vec<Vec<char>>, Vec<Operation>) {
+        let (mut stacks, mut operations) = crate::example();
+        stacks[2].insert(0, ' ');
+        operations.push("swap 1 2".parse().unwrap());
+        operations.push("reverse 3".parse().unwrap());
+        operations.push("move 4 from 3 to 1".parse().unwrap());
+        (stacks, operations)
+    }
+
+    // A file path for a test, the file is removed when dropped even if the test panics
+    struct TempFile(std::path::PathBuf);
+
+    impl TempFile {
+        fn new(name: &str) -> Self {
+            TempFile(std::env::temp_dir().join(format!(
+                "aoc22-day5-{}-{}",
+                name,
+                std::process::id()
+            )))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn replay(stacks: &[Vec<char>], operations: &[Operation]) -> Vec<Vec<char>> {
+        let mut stacks = stacks.to_vec();
+        execute_all(&CrateMover9001, &mut stacks, operations, Mode::Strict).unwrap();
+        stacks
+    }
+
+    #[test]
+    fn test_state_at_matches_replay() {
+        let (stacks, operations) = example();
+        for interval in 1..=8 {
+            let checkpoints = Checkpoints::build(
+                &CrateMover9001,
+                &operations,
+                stacks.clone(),
+                Mode::Strict,
+                interval,
+            )
+            .unwrap();
+            for step in 0..=operations.len() {
+                assert_eq!(
+                    checkpoints.state_at(step).unwrap(),
+                    replay(&stacks, &operations[..step])
+                );
+            }
+            assert!(checkpoints.state_at(operations.len() + 1).is_err());
+        }
+    }
+
+    #[test]
+    fn test_build_rejects_invalid() {
+        let (stacks, operations) = example();
+        assert!(Checkpoints::build(
+            &CrateMover9001,
+            &operations,
+            stacks.clone(),
+            Mode::Strict,
+            0
+        )
+        .is_err());
+        assert!(
+            Checkpoints::build(&CrateMover9001, &operations, vec![vec![]], Mode::Strict, 2)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let (stacks, operations) = example();
+        let file = TempFile::new("checkpoints");
+        let path = &file.0;
+
+        let checkpoints = Checkpoints::build(
+            &CrateMover9001,
+            &operations,
+            stacks.clone(),
+            Mode::Strict,
+            3,
+        )
+        .unwrap();
+        checkpoints.save(path).unwrap();
+
+        let load = |crane: &dyn Crane, operations, start: &[Vec<char>], mode| {
+            Checkpoints::load(path, crane, operations, start, mode).map(|c| c.states)
+        };
+        let loaded =
+            Checkpoints::load(path, &CrateMover9001, &operations, &stacks, Mode::Strict).unwrap();
+        assert_eq!(loaded.interval, 3);
+        assert_eq!(loaded.states, checkpoints.states);
+        assert_eq!(
+            loaded.state_at(5).unwrap(),
+            checkpoints.state_at(5).unwrap()
+        );
+
+        // Checkpoints of other instructions, cranes, modes or stacks are rejected
+        assert!(load(&CrateMover9001, &operations[1..], &stacks, Mode::Strict).is_err());
+        let mut edited = operations.clone();
+        edited[3] = "move 1 from 3 to 2".parse().unwrap();
+        assert!(load(&CrateMover9001, &edited, &stacks, Mode::Strict).is_err());
+        assert!(load(&CrateMover9000, &operations, &stacks, Mode::Strict).is_err());
+        assert!(load(&CrateMover9001, &operations, &stacks, Mode::Lenient).is_err());
+        let mut other = stacks.clone();
+        other.swap(0, 1);
+        assert!(load(&CrateMover9001, &operations, &other, Mode::Strict).is_err());
+        assert!(load(&CrateMover9001, &operations, &stacks[1..], Mode::Strict).is_err());
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        let (_, operations) = example();
+        assert_eq!(fingerprint(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fingerprint(&operations), fingerprint(&operations.clone()));
+        assert_ne!(fingerprint(&operations), fingerprint(&operations[1..]));
+
+        let mut swapped = operations.clone();
+        swapped.swap(0, 1);
+        assert_ne!(fingerprint(&operations), fingerprint(&swapped));
+    }
+
+    #[test]
+    fn test_parse_checkpoint() {
+        assert_eq!(
+            parse_checkpoint("6 2:ZN 0: 3: PD", 3),
+            Ok((6, vec![vec!['Z', 'N'], vec![], vec![' ', 'P', 'D']]))
+        );
+        assert_eq!(parse_checkpoint("0", 0), Ok((0, vec![])));
+        assert!(parse_checkpoint("6 2:ZN 0: 3: PD", 2).is_err());
+        assert!(parse_checkpoint("0 1:A", 2).is_err());
+        assert!(parse_checkpoint("x 1:A", 1).is_err());
+        assert!(parse_checkpoint("0 3:AB", 1).is_err());
+        assert!(parse_checkpoint("0 AB", 1).is_err());
+    }
+}
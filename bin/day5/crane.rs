@@ -0,0 +1,458 @@
+use crate::{
+    operation::Operation, process_instructions_part1, process_instructions_part2, Instruction,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    // Reject instructions referring to missing stacks or crates
+    Strict,
+    // Skip instructions referring to missing stacks and move as many crates as there are
+    Lenient,
+}
+
+pub trait Crane {
+    fn name(&self) -> String;
+    fn execute(&self, stacks: &mut [Vec<char>], instr: &Instruction);
+    // Reverts `execute` of the same instruction
+    fn undo(&self, stacks: &mut [Vec<char>], instr: &Instruction);
+}
+
+pub fn inverse(instr: &Instruction) -> Instruction {
+    Instruction {
+        amount: instr.amount,
+        from: instr.to,
+        to: instr.from,
+    }
+}
+
+// Moves one crate at a time, reversing the order of the moved crates
+pub struct CrateMover9000;
+
+impl Crane for CrateMover9000 {
+    fn name(&self) -> String {
+        String::from("CrateMover 9000")
+    }
+
+    fn execute(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        process_instructions_part1(stacks, instr);
+    }
+
+    fn undo(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        process_instructions_part1(stacks, &inverse(instr));
+    }
+}
+
+// Moves all crates at once, keeping their order
+pub struct CrateMover9001;
+
+impl Crane for CrateMover9001 {
+    fn name(&self) -> String {
+        String::from("CrateMover 9001")
+    }
+
+    fn execute(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        process_instructions_part2(stacks, instr);
+    }
+
+    fn undo(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        process_instructions_part2(stacks, &inverse(instr));
+    }
+}
+
+fn batches(instr: &Instruction, capacity: u32) -> impl Iterator<Item = Instruction> + '_ {
+    let mut remaining = instr.amount;
+    std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+
+        let amount = remaining.min(capacity);
+        remaining -= amount;
+        Some(Instruction {
+            amount,
+            from: instr.from,
+            to: instr.to,
+        })
+    })
+}
+
+// Moves up to `capacity` crates at once, keeping their order within a batch
+pub struct CapacityCrane {
+    pub capacity: u32,
+}
+
+impl Crane for CapacityCrane {
+    fn name(&self) -> String {
+        format!("Capacity crane ({})", self.capacity)
+    }
+
+    fn execute(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        for batch in batches(instr, self.capacity) {
+            process_instructions_part2(stacks, &batch);
+        }
+    }
+
+    fn undo(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        let batches: Vec<Instruction> = batches(instr, self.capacity).collect();
+        for batch in batches.iter().rev() {
+            process_instructions_part2(stacks, &inverse(batch));
+        }
+    }
+}
+
+// Moves up to `batch` crates at once, reversing the order of the 1st, 3rd, 5th, ... batch
+pub struct OddReversingCrane {
+    pub batch: u32,
+}
+
+impl Crane for OddReversingCrane {
+    fn name(&self) -> String {
+        format!("Odd reversing crane ({})", self.batch)
+    }
+
+    fn execute(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        for (i, batch) in batches(instr, self.batch).enumerate() {
+            if i % 2 == 0 {
+                process_instructions_part1(stacks, &batch);
+            } else {
+                process_instructions_part2(stacks, &batch);
+            }
+        }
+    }
+
+    fn undo(&self, stacks: &mut [Vec<char>], instr: &Instruction) {
+        let batches: Vec<Instruction> = batches(instr, self.batch).collect();
+        for (i, batch) in batches.iter().enumerate().rev() {
+            if i % 2 == 0 {
+                process_instructions_part1(stacks, &inverse(batch));
+            } else {
+                process_instructions_part2(stacks, &inverse(batch));
+            }
+        }
+    }
+}
+
+pub fn describe_stacks(stacks: &[Vec<char>]) -> String {
+    stacks
+        .iter()
+        .enumerate()
+        .map(|(i, s)| format!("{}:[{}]", i + 1, s.iter().collect::<String>()))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn check_stack(stacks: &[Vec<char>], stack: u32) -> Result<(), String> {
+    if stack == 0 || stack as usize > stacks.len() {
+        return Err(format!(
+            "stack {} does not exist, there are {} stacks",
+            stack,
+            stacks.len()
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_instruction(stacks: &[Vec<char>], instr: &Instruction) -> Result<(), String> {
+    check_stack(stacks, instr.from)?;
+    check_stack(stacks, instr.to)?;
+
+    let available = stacks[instr.from as usize - 1].len();
+    if instr.amount as usize > available {
+        return Err(format!(
+            "cannot move {} crates from stack {} holding {}",
+            instr.amount, instr.from, available
+        ));
+    }
+
+    Ok(())
+}
+
+pub fn check_operation(stacks: &[Vec<char>], op: &Operation) -> Result<(), String> {
+    match op {
+        Operation::Move(instr) => check_instruction(stacks, instr),
+        Operation::Swap(a, b) => check_stack(stacks, *a).and(check_stack(stacks, *b)),
+        Operation::Reverse(s) => check_stack(stacks, *s),
+    }
+}
+
+// The operation taking the stacks back to before `op`, swaps and reverses are their own inverse
+pub fn inverse_operation(op: &Operation) -> Operation {
+    match op {
+        Operation::Move(instr) => Operation::Move(inverse(instr)),
+        op => op.clone(),
+    }
+}
+
+fn apply(crane: &dyn Crane, stacks: &mut [Vec<char>], op: &Operation) {
+    match op {
+        Operation::Move(instr) => crane.execute(stacks, instr),
+        Operation::Swap(a, b) => stacks.swap(*a as usize - 1, *b as usize - 1),
+        Operation::Reverse(s) => stacks[*s as usize - 1].reverse(),
+    }
+}
+
+// Reverts an operation previously executed with `execute`
+pub fn undo(crane: &dyn Crane, stacks: &mut [Vec<char>], op: &Operation) {
+    match op {
+        Operation::Move(instr) => crane.undo(stacks, instr),
+        op => apply(crane, stacks, op),
+    }
+}
+
+// Executes operation number `step` (1-based), validating it against the stacks first.
+// Returns the operation that was actually executed, if any.
+pub fn execute(
+    crane: &dyn Crane,
+    stacks: &mut [Vec<char>],
+    step: usize,
+    op: &Operation,
+    mode: Mode,
+) -> Result<Option<Operation>, String> {
+    match (check_operation(stacks, op), mode) {
+        (Ok(()), _) => {
+            apply(crane, stacks, op);
+            Ok(Some(op.clone()))
+        }
+        (Err(e), Mode::Strict) => Err(format!(
+            "Instruction {} ({}): {}, stacks: {}",
+            step,
+            op,
+            e,
+            describe_stacks(stacks)
+        )),
+        (Err(_), Mode::Lenient) => match op {
+            Operation::Move(instr)
+                if check_stack(stacks, instr.from).is_ok()
+                    && check_stack(stacks, instr.to).is_ok() =>
+            {
+                let clamped = Instruction {
+                    amount: instr
+                        .amount
+                        .min(stacks[instr.from as usize - 1].len() as u32),
+                    from: instr.from,
+                    to: instr.to,
+                };
+                crane.execute(stacks, &clamped);
+                Ok(Some(Operation::Move(clamped)))
+            }
+            _ => Ok(None),
+        },
+    }
+}
+
+pub fn execute_all(
+    crane: &dyn Crane,
+    stacks: &mut [Vec<char>],
+    operations: &[Operation],
+    mode: Mode,
+) -> Result<(), String> {
+    for (i, op) in operations.iter().enumerate() {
+        execute(crane, stacks, i + 1, op, mode)?;
+    }
+    Ok(())
+}
+
+fn parse_batch_size(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(0) => Err(String::from("Batch size must be at least 1")),
+        Ok(n) => Ok(n),
+        Err(e) => Err(format!("Invalid batch size '{}': {}", s, e)),
+    }
+}
+
+// Parses a crane model from the command line: 9000, 9001, capacity:N or odd-reverse:N
+pub fn parse_crane(spec: &str) -> Result<Box<dyn Crane>, String> {
+    match spec.split_once(':') {
+        None if spec == "9000" => Ok(Box::new(CrateMover9000)),
+        None if spec == "9001" => Ok(Box::new(CrateMover9001)),
+        Some(("capacity", n)) => Ok(Box::new(CapacityCrane {
+            capacity: parse_batch_size(n)?,
+        })),
+        Some(("odd-reverse", n)) => Ok(Box::new(OddReversingCrane {
+            batch: parse_batch_size(n)?,
+        })),
+        _ => Err(format!("Unknown crane '{}'", spec)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::example;
+
+    fn run(crane: &dyn Crane, stacks: &[Vec<char>], instr: &str) -> Vec<Vec<char>> {
+        let mut stacks = stacks.to_vec();
+        crane.execute(&mut stacks, &instr.parse().unwrap());
+        stacks
+    }
+
+    #[test]
+    fn test_capacity_crane_moves_in_batches() {
+        let stacks = vec![vec!['A', 'B', 'C', 'D', 'E'], vec![]];
+        let crane = CapacityCrane { capacity: 2 };
+        assert_eq!(
+            run(&crane, &stacks, "move 5 from 1 to 2"),
+            vec![vec![], vec!['D', 'E', 'B', 'C', 'A']]
+        );
+    }
+
+    #[test]
+    fn test_capacity_crane_matches_crate_movers() {
+        let stacks = vec![vec!['A', 'B', 'C', 'D', 'E'], vec!['F']];
+        let instr = "move 4 from 1 to 2";
+        assert_eq!(
+            run(&CapacityCrane { capacity: 1 }, &stacks, instr),
+            run(&CrateMover9000, &stacks, instr)
+        );
+        assert_eq!(
+            run(&CapacityCrane { capacity: 4 }, &stacks, instr),
+            run(&CrateMover9001, &stacks, instr)
+        );
+    }
+
+    #[test]
+    fn test_odd_reversing_crane() {
+        let stacks = vec![vec!['A', 'B', 'C', 'D', 'E'], vec![]];
+        let crane = OddReversingCrane { batch: 2 };
+        assert_eq!(
+            run(&crane, &stacks, "move 5 from 1 to 2"),
+            vec![vec![], vec!['E', 'D', 'B', 'C', 'A']]
+        );
+    }
+
+    #[test]
+    fn test_execute_all_valid_instructions() {
+        let (mut stacks, instructions) = example();
+        execute_all(&CrateMover9001, &mut stacks, &instructions, Mode::Strict).unwrap();
+        assert_eq!(stacks, vec![vec!['M'], vec!['C'], vec!['P', 'Z', 'N', 'D']]);
+    }
+
+    #[test]
+    fn test_execute_strict_rejects_too_many_crates() {
+        let (mut stacks, mut instructions) = example();
+        instructions.insert(1, "move 4 from 1 to 3".parse().unwrap());
+
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut stacks = stacks.clone();
+            let err = execute_all(crane, &mut stacks, &instructions, Mode::Strict).unwrap_err();
+            assert_eq!(
+                err,
+                "Instruction 2 (move 4 from 1 to 3): cannot move 4 crates from stack 1 holding 3, \
+                 stacks: 1:[ZND] 2:[MC] 3:[P]"
+            );
+        }
+
+        let err = execute(
+            &CrateMover9000,
+            &mut stacks,
+            1,
+            &"move 1 from 0 to 1".parse().unwrap(),
+            Mode::Strict,
+        )
+        .unwrap_err();
+        assert!(err.contains("stack 0 does not exist"));
+    }
+
+    #[test]
+    fn test_execute_strict_rejects_missing_stacks() {
+        let (mut stacks, _) = example();
+        for instr in ["move 1 from 0 to 1", "move 1 from 1 to 4"] {
+            let err = execute(
+                &CrateMover9000,
+                &mut stacks,
+                7,
+                &instr.parse().unwrap(),
+                Mode::Strict,
+            )
+            .unwrap_err();
+            assert!(err.starts_with(&format!("Instruction 7 ({})", instr)));
+            assert!(err.contains("does not exist, there are 3 stacks"));
+        }
+    }
+
+    #[test]
+    fn test_execute_lenient() {
+        let (mut stacks, _) = example();
+        let instructions = vec![
+            "move 5 from 2 to 1".parse().unwrap(),
+            "move 1 from 0 to 1".parse().unwrap(),
+            "move 1 from 1 to 9".parse().unwrap(),
+        ];
+        execute_all(&CrateMover9001, &mut stacks, &instructions, Mode::Lenient).unwrap();
+        assert_eq!(
+            stacks,
+            vec![vec!['Z', 'N', 'M', 'C', 'D'], vec![], vec!['P']]
+        );
+    }
+
+    #[test]
+    fn test_undo_restores_stacks() {
+        let stacks = vec![vec!['A', 'B', 'C', 'D', 'E'], vec!['F']];
+        let instr: Instruction = "move 5 from 1 to 2".parse().unwrap();
+        let cranes: Vec<Box<dyn Crane>> = vec![
+            Box::new(CrateMover9000),
+            Box::new(CrateMover9001),
+            Box::new(CapacityCrane { capacity: 2 }),
+            Box::new(OddReversingCrane { batch: 2 }),
+        ];
+
+        for crane in cranes {
+            let mut s = stacks.clone();
+            crane.execute(&mut s, &instr);
+            assert_ne!(s, stacks);
+            crane.undo(&mut s, &instr);
+            assert_eq!(s, stacks, "{}", crane.name());
+        }
+    }
+
+    #[test]
+    fn test_execute_swap_and_reverse() {
+        let (mut stacks, _) = example();
+        let operations = vec![
+            "swap 1 3".parse().unwrap(),
+            "reverse 2".parse().unwrap(),
+            "move 2 from 2 to 1".parse().unwrap(),
+        ];
+        execute_all(&CrateMover9001, &mut stacks, &operations, Mode::Strict).unwrap();
+        assert_eq!(stacks, vec![vec!['P', 'C', 'M'], vec!['D'], vec!['Z', 'N']]);
+
+        for op in &operations {
+            assert_eq!(inverse_operation(&inverse_operation(op)), *op);
+        }
+        undo(&CrateMover9001, &mut stacks, &operations[1]);
+        assert_eq!(stacks[1], vec!['D']);
+        undo(&CrateMover9001, &mut stacks, &operations[0]);
+        assert_eq!(stacks, vec![vec!['Z', 'N'], vec!['D'], vec!['P', 'C', 'M']]);
+    }
+
+    #[test]
+    fn test_execute_swap_and_reverse_missing_stacks() {
+        let (mut stacks, _) = example();
+        for op in ["swap 1 4", "reverse 0"] {
+            let op: Operation = op.parse().unwrap();
+            let err = execute(&CrateMover9000, &mut stacks, 1, &op, Mode::Strict).unwrap_err();
+            assert!(err.contains("does not exist, there are 3 stacks"));
+            assert_eq!(
+                execute(&CrateMover9000, &mut stacks, 1, &op, Mode::Lenient),
+                Ok(None)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_crane() {
+        assert_eq!(parse_crane("9000").unwrap().name(), "CrateMover 9000");
+        assert_eq!(parse_crane("9001").unwrap().name(), "CrateMover 9001");
+        assert_eq!(
+            parse_crane("capacity:3").unwrap().name(),
+            "Capacity crane (3)"
+        );
+        assert_eq!(
+            parse_crane("odd-reverse:2").unwrap().name(),
+            "Odd reversing crane (2)"
+        );
+        assert!(parse_crane("9002").is_err());
+        assert!(parse_crane("capacity:0").is_err());
+        assert!(parse_crane("capacity:x").is_err());
+    }
+}
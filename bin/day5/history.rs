@@ -0,0 +1,214 @@
+use crate::{
+    crane::{check_operation, describe_stacks, execute, inverse_operation, undo, Crane, Mode},
+    operation::Operation,
+};
+
+// Stack state positioned somewhere within a list of operations, which can be
+// stepped forward (redo) and backward (undo).
+pub struct History<'a> {
+    crane: &'a dyn Crane,
+    operations: &'a [Operation],
+    mode: Mode,
+    stacks: Vec<Vec<char>>,
+    // The operations as actually executed, `applied[i]` is `None` when
+    // operation `i` was skipped in lenient mode
+    applied: Vec<Option<Operation>>,
+}
+
+impl<'a> History<'a> {
+    // Starts before the first operation
+    pub fn new(
+        crane: &'a dyn Crane,
+        operations: &'a [Operation],
+        stacks: Vec<Vec<char>>,
+        mode: Mode,
+    ) -> Self {
+        Self {
+            crane,
+            operations,
+            mode,
+            stacks,
+            applied: vec![],
+        }
+    }
+
+    // Starts after the last operation, with `stacks` being the final state
+    pub fn from_end(
+        crane: &'a dyn Crane,
+        operations: &'a [Operation],
+        stacks: Vec<Vec<char>>,
+    ) -> Self {
+        Self {
+            crane,
+            operations,
+            mode: Mode::Strict,
+            stacks,
+            applied: operations.iter().cloned().map(Some).collect(),
+        }
+    }
+
+    pub fn stacks(&self) -> &[Vec<char>] {
+        &self.stacks
+    }
+
+    // The last operation as actually executed, `None` at the start or when it
+    // was skipped in lenient mode
+    pub fn last_applied(&self) -> Option<&Operation> {
+        self.applied.last().and_then(Option::as_ref)
+    }
+
+    // Number of operations executed so far
+    pub fn position(&self) -> usize {
+        self.applied.len()
+    }
+
+    // Executes the next operation, returns false when already at the end
+    pub fn forward(&mut self) -> Result<bool, String> {
+        let step = self.position();
+        let Some(op) = self.operations.get(step) else {
+            return Ok(false);
+        };
+
+        let applied = execute(self.crane, &mut self.stacks, step + 1, op, self.mode)?;
+        self.applied.push(applied);
+        Ok(true)
+    }
+
+    // Reverts the last executed operation, returns false when already at the start
+    pub fn back(&mut self) -> Result<bool, String> {
+        let step = self.position();
+        let Some(applied) = self.applied.last() else {
+            return Ok(false);
+        };
+
+        if let Some(op) = applied {
+            check_operation(&self.stacks, &inverse_operation(op)).map_err(|e| {
+                format!(
+                    "Undo of instruction {} ({}): {}, stacks: {}",
+                    step,
+                    op,
+                    e,
+                    describe_stacks(&self.stacks)
+                )
+            })?;
+            undo(self.crane, &mut self.stacks, op);
+        }
+
+        self.applied.pop();
+        Ok(true)
+    }
+
+    pub fn seek(&mut self, position: usize) -> Result<(), String> {
+        while self.position() < position && self.forward()? {}
+        while self.position() > position && self.back()? {}
+        Ok(())
+    }
+}
+
+// Recovers the starting stacks from the final stacks by running the operations backwards
+pub fn reverse_all(
+    crane: &dyn Crane,
+    operations: &[Operation],
+    stacks: Vec<Vec<char>>,
+) -> Result<Vec<Vec<char>>, String> {
+    let mut history = History::from_end(crane, operations, stacks);
+    history.seek(0)?;
+    Ok(history.stacks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crane::{CrateMover9000, CrateMover9001},
+        example,
+    };
+
+    #[test]
+    fn test_history_forward_and_back() {
+        let (stacks, instructions) = example();
+        let mut history =
+            History::new(&CrateMover9000, &instructions, stacks.clone(), Mode::Strict);
+
+        assert!(!history.back().unwrap());
+        history.seek(4).unwrap();
+        assert_eq!(history.position(), 4);
+        assert_eq!(
+            history.stacks(),
+            &[vec!['C'], vec!['M'], vec!['P', 'D', 'N', 'Z']]
+        );
+        assert!(!history.forward().unwrap());
+
+        assert!(history.back().unwrap());
+        assert!(history.back().unwrap());
+        assert_eq!(
+            history.stacks(),
+            &[vec![], vec!['M', 'C'], vec!['P', 'D', 'N', 'Z']]
+        );
+
+        assert!(history.forward().unwrap());
+        history.seek(0).unwrap();
+        assert_eq!(history.stacks(), stacks.as_slice());
+    }
+
+    #[test]
+    fn test_reverse_all_recovers_start() {
+        let (stacks, instructions) = example();
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let mut history = History::new(crane, &instructions, stacks.clone(), Mode::Strict);
+            history.seek(instructions.len()).unwrap();
+
+            let end = history.stacks().to_vec();
+            assert_eq!(reverse_all(crane, &instructions, end).unwrap(), stacks);
+        }
+    }
+
+    #[test]
+    fn test_reverse_all_rejects_impossible_undo() {
+        let (_, instructions) = example();
+        let end = vec![vec!['C'], vec![], vec!['P', 'D', 'N', 'Z']];
+        let err = reverse_all(&CrateMover9000, &instructions, end).unwrap_err();
+        assert!(err.starts_with("Undo of instruction 4 (move 1 from 1 to 2)"));
+    }
+
+    #[test]
+    fn test_reverse_all_with_swap_and_reverse() {
+        let (stacks, mut operations) = example();
+        operations.push("swap 1 3".parse().unwrap());
+        operations.push("reverse 3".parse().unwrap());
+
+        let mut history = History::new(&CrateMover9000, &operations, stacks.clone(), Mode::Strict);
+        history.seek(operations.len()).unwrap();
+        let end = history.stacks().to_vec();
+        assert_eq!(
+            reverse_all(&CrateMover9000, &operations, end).unwrap(),
+            stacks
+        );
+    }
+
+    #[test]
+    fn test_history_lenient_undo_uses_clamped_instruction() {
+        let stacks = vec![vec!['A'], vec!['B']];
+        let instructions = vec![
+            "move 3 from 1 to 2".parse().unwrap(),
+            "move 1 from 1 to 9".parse().unwrap(),
+        ];
+        let mut history = History::new(
+            &CrateMover9001,
+            &instructions,
+            stacks.clone(),
+            Mode::Lenient,
+        );
+
+        history.seek(1).unwrap();
+        assert_eq!(
+            history.last_applied(),
+            Some(&"move 1 from 1 to 2".parse().unwrap())
+        );
+        history.seek(2).unwrap();
+        assert_eq!(history.stacks(), &[vec![], vec!['B', 'A']]);
+        assert_eq!(history.last_applied(), None);
+        history.seek(0).unwrap();
+        assert_eq!(history.stacks(), stacks.as_slice());
+    }
+}
@@ -1,12 +1,21 @@
+mod animate;
+mod checkpoint;
+mod crane;
+mod history;
+mod operation;
+mod optimize;
+
+use crane::{execute, execute_all, parse_crane, Crane, CrateMover9000, CrateMover9001, Mode};
+use operation::{parse_operations, Operation};
 use std::{
+    fmt,
     fs::File,
     io::{self, BufRead, BufReader},
     path::Path,
     str::FromStr,
-    vec,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 struct Instruction {
     amount: u32,
     from: u32,
@@ -17,23 +26,68 @@ impl FromStr for Instruction {
     type Err = String;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens: Vec<&str> = s.split_whitespace().collect();
-        if tokens.len() > 6 {
-            return Err(format!("To many tokens {}", "hello"));
+        match s.parse::<Operation>()? {
+            Operation::Move(instr) => Ok(instr),
+            op => Err(format!("Expected a move instruction, found '{}'", op)),
         }
+    }
+}
 
-        assert_eq!(tokens[0], "move");
-        assert_eq!(tokens[2], "from");
-        assert_eq!(tokens[4], "to");
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "move {} from {} to {}", self.amount, self.from, self.to)
+    }
+}
 
-        let amount = tokens[1].parse::<u32>().map_err(|e| e.to_string())?;
-        let from = tokens[3].parse::<u32>().map_err(|e| e.to_string())?;
-        let to = tokens[5].parse::<u32>().map_err(|e| e.to_string())?;
-        Ok(Instruction { amount, from, to })
+// Stack labels in the footer are either their numbers or unique names
+fn parse_labels(footer: &str) -> Result<Vec<String>, String> {
+    let labels: Vec<String> = footer.split_whitespace().map(String::from).collect();
+    for (i, label) in labels.iter().enumerate() {
+        let numeric = label.chars().all(|c| c.is_ascii_digit());
+        if (numeric && label.parse::<usize>() != Ok(i + 1)) || labels[..i].contains(label) {
+            return Err(format!("Unexpected stack label '{}' in footer", label));
+        }
     }
+    Ok(labels)
 }
 
-fn process_instructions_part1(stacks: &mut Vec<Vec<char>>, instr: &Instruction) {
+fn parse_stacks(drawing: &[String]) -> Result<Vec<Vec<char>>, String> {
+    let (footer, rows) = drawing
+        .split_last()
+        .ok_or_else(|| String::from("Missing stack drawing"))?;
+
+    let stack_num = parse_labels(footer)?.len();
+
+    let mut stacks: Vec<Vec<char>> = vec![vec![]; stack_num];
+    for (height, row) in rows.iter().rev().enumerate() {
+        let cells: Vec<char> = row.chars().collect();
+        for (i, cell) in cells.chunks(4).enumerate() {
+            let c = match cell {
+                [' ', ' ', ' '] | [' ', ' ', ' ', ' '] => continue,
+                ['[', c, ']'] | ['[', c, ']', ' '] => *c,
+                _ => {
+                    return Err(format!(
+                        "Malformed crate '{}' in row '{}'",
+                        cell.iter().collect::<String>(),
+                        row
+                    ))
+                }
+            };
+
+            let stack = stacks
+                .get_mut(i)
+                .ok_or_else(|| format!("Crate '{}' is outside of the {} stacks", c, stack_num))?;
+            if stack.len() != height {
+                return Err(format!("Crate '{}' in stack {} is floating", c, i + 1));
+            }
+            stack.push(c);
+        }
+    }
+
+    Ok(stacks)
+}
+
+fn process_instructions_part1(stacks: &mut [Vec<char>], instr: &Instruction) {
     for _ in 0..instr.amount {
         let stack_from: &mut Vec<char> = &mut stacks[(instr.from - 1) as usize];
         let last = stack_from.pop();
@@ -45,48 +99,264 @@ fn process_instructions_part1(stacks: &mut Vec<Vec<char>>, instr: &Instruction)
     }
 }
 
-fn process_instructions_part2(stacks: &mut Vec<Vec<char>>, instr: &Instruction) {
+fn process_instructions_part2(stacks: &mut [Vec<char>], instr: &Instruction) {
     let stack_from: &mut Vec<char> = &mut stacks[(instr.from - 1) as usize];
-    let mut stack_split = stack_from.split_off(stack_from.len() - instr.amount as usize);
+    let mut stack_split =
+        stack_from.split_off(stack_from.len().saturating_sub(instr.amount as usize));
     let stack_to: &mut Vec<char> = &mut stacks[(instr.to - 1) as usize];
     stack_to.append(&mut stack_split);
 }
 
-fn print_stack(stacks: &Vec<Vec<char>>) {
-    let mut out: String = String::new();
-    for s in stacks {
-        if let Some(c) = s.last() {
-            out.push(*c);
-        }
-    }
-    println!("{}", out);
+fn top_of_stacks(stacks: &[Vec<char>]) -> String {
+    stacks.iter().filter_map(|s| s.last()).collect()
 }
 
-fn main() -> io::Result<()> {
-    let start_stacks: Vec<Vec<char>> = vec![
-        vec!['J', 'H', 'P', 'M', 'S', 'F', 'N', 'V'],
-        vec!['S', 'R', 'L', 'M', 'J', 'D', 'Q'],
-        vec!['N', 'Q', 'D', 'H', 'C', 'S', 'W', 'B'],
-        vec!['R', 'S', 'C', 'L'],
-        vec!['M', 'V', 'T', 'P', 'F', 'B'],
-        vec!['T', 'R', 'Q', 'N', 'C'],
-        vec!['G', 'V', 'R'],
-        vec!['C', 'Z', 'S', 'P', 'D', 'L', 'R'],
-        vec!['D', 'S', 'J', 'V', 'G', 'P', 'B', 'F'],
+fn print_stack(stacks: &[Vec<char>]) {
+    println!("{}", top_of_stacks(stacks));
+}
+
+fn render_stacks(stacks: &[Vec<char>], labels: &[String]) -> String {
+    render_stacks_with(stacks, labels, |_, _, c| format!("[{}]", c))
+}
+
+// Draws the stacks above a footer of their `labels`, as parsed from the drawing
+fn render_stacks_with<F>(stacks: &[Vec<char>], labels: &[String], render_crate: F) -> String
+where
+    F: Fn(usize, usize, char) -> String,
+{
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+
+    let mut rows: Vec<String> = (0..height)
+        .rev()
+        .map(|h| {
+            stacks
+                .iter()
+                .enumerate()
+                .map(|(i, s)| match s.get(h) {
+                    Some(c) => render_crate(i, h, *c),
+                    None => String::from("   "),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect();
+
+    let footer = labels
+        .iter()
+        .map(|label| format!("{:^3}", label))
+        .collect::<Vec<String>>()
+        .join(" ");
+    rows.push(footer);
+
+    rows.join("\n")
+}
+
+fn arg_values<'a>(args: &'a [String], name: &'a str) -> impl Iterator<Item = &'a str> {
+    args.windows(2)
+        .filter(move |w| w[0] == name)
+        .map(|w| w[1].as_str())
+}
+
+fn arg_value<'a>(args: &'a [String], name: &'a str) -> Option<&'a str> {
+    arg_values(args, name).next()
+}
+
+fn invalid_data(e: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+// The stacks and moves of the puzzle example, shared by the tests of all modules
+#[cfg(test)]
+fn example() -> (Vec<Vec<char>>, Vec<Operation>) {
+    let stacks = vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']];
+    let operations = vec![
+        "move 1 from 2 to 1".parse().unwrap(),
+        "move 3 from 1 to 3".parse().unwrap(),
+        "move 2 from 2 to 1".parse().unwrap(),
+        "move 1 from 1 to 2".parse().unwrap(),
     ];
+    (stacks, operations)
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let dump = args.iter().any(|a| a == "--dump");
+    let mode = match (
+        args.iter().any(|a| a == "--strict"),
+        args.iter().any(|a| a == "--lenient"),
+    ) {
+        (true, true) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "--strict and --lenient cannot be combined",
+            ))
+        }
+        (_, true) => Mode::Lenient,
+        _ => Mode::Strict,
+    };
+
+    let path = Path::new(arg_value(&args, "--input").unwrap_or("./bin/day5/input"));
+    let file = File::open(path)?;
+    let lines: Vec<String> = BufReader::new(file).lines().collect::<io::Result<_>>()?;
+
+    // The drawing and the instructions are separated by a blank line
+    let blank = lines
+        .iter()
+        .position(|l| l.trim().is_empty())
+        .unwrap_or(lines.len());
+    let start_stacks = parse_stacks(&lines[..blank]).map_err(invalid_data)?;
+    let labels = parse_labels(&lines[blank - 1]).map_err(invalid_data)?;
+
+    let first_line = (blank + 2).min(lines.len() + 1);
+    let operations =
+        parse_operations(&lines[first_line - 1..], &labels, first_line).map_err(invalid_data)?;
+
+    let cranes: Vec<Box<dyn Crane>> = arg_values(&args, "--crane")
+        .map(parse_crane)
+        .collect::<Result<_, _>>()
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+    if args.iter().any(|a| a == "--animate") {
+        let crane = cranes
+            .first()
+            .map_or(&CrateMover9000 as &dyn Crane, |c| c.as_ref());
+        let delay = arg_value(&args, "--delay")
+            .map(|d| {
+                d.parse::<u64>()
+                    .map_err(|e| invalid_data(format!("Invalid delay '{}': {}", d, e)))
+            })
+            .transpose()?
+            .unwrap_or(200);
+        return animate::run(start_stacks, &labels, &operations, crane, mode, delay);
+    }
+
+    if let Some(step) = arg_value(&args, "--state-at") {
+        let step = step
+            .parse::<usize>()
+            .map_err(|e| invalid_data(format!("Invalid step '{}': {}", step, e)))?;
+        let crane = cranes
+            .first()
+            .map_or(&CrateMover9000 as &dyn Crane, |c| c.as_ref());
+        let interval = arg_value(&args, "--checkpoint-interval")
+            .map(|i| {
+                i.parse::<usize>().map_err(|e| {
+                    invalid_data(format!("Invalid checkpoint interval '{}': {}", i, e))
+                })
+            })
+            .transpose()?;
+
+        // Reuse checkpoints from an earlier run when a checkpoint file exists
+        let checkpoints = match arg_value(&args, "--checkpoint-file").map(Path::new) {
+            Some(file) if file.exists() => {
+                let checkpoints =
+                    checkpoint::Checkpoints::load(file, crane, &operations, &start_stacks, mode)?;
+                match interval {
+                    Some(interval) if interval != checkpoints.interval() => {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "{} holds checkpoints every {} operations, not every {}",
+                                file.display(),
+                                checkpoints.interval(),
+                                interval
+                            ),
+                        ))
+                    }
+                    _ => checkpoints,
+                }
+            }
+            file => {
+                let checkpoints = checkpoint::Checkpoints::build(
+                    crane,
+                    &operations,
+                    start_stacks,
+                    mode,
+                    interval.unwrap_or(1000),
+                )
+                .map_err(invalid_data)?;
+                if let Some(file) = file {
+                    checkpoints.save(file)?;
+                }
+                checkpoints
+            }
+        };
+
+        let stacks = checkpoints.state_at(step).map_err(invalid_data)?;
+        println!("{}", render_stacks(&stacks, &labels));
+        return Ok(());
+    }
+
+    if args.iter().any(|a| a == "--reverse") {
+        // The drawing is the final state, recover the state before the instructions
+        let crane = cranes
+            .first()
+            .map_or(&CrateMover9000 as &dyn Crane, |c| c.as_ref());
+        let stacks =
+            history::reverse_all(crane, &operations, start_stacks).map_err(invalid_data)?;
+        println!("{}", render_stacks(&stacks, &labels));
+        return Ok(());
+    }
+
+    if args.iter().any(|a| a == "--optimize") {
+        let crane = cranes
+            .first()
+            .map_or(&CrateMover9000 as &dyn Crane, |c| c.as_ref());
+        let instructions = operations
+            .iter()
+            .map(|op| match op {
+                Operation::Move(instr) => Ok(instr.clone()),
+                op => Err(format!(
+                    "Cannot optimize '{}', only moves are supported",
+                    op
+                )),
+            })
+            .collect::<Result<Vec<Instruction>, String>>()
+            .map_err(invalid_data)?;
+        let max_states = arg_value(&args, "--max-states")
+            .map(|m| {
+                m.parse::<usize>()
+                    .map_err(|e| invalid_data(format!("Invalid max states '{}': {}", m, e)))
+            })
+            .transpose()?
+            .unwrap_or(100_000);
+        let result = optimize::optimize(crane, &start_stacks, &instructions, max_states)
+            .map_err(invalid_data)?;
+
+        for instr in &result.instructions {
+            println!("{}", instr);
+        }
+        println!(
+            "{}: {} moves instead of {}, {} saved{}",
+            crane.name(),
+            result.instructions.len(),
+            instructions.len(),
+            instructions.len() - result.instructions.len(),
+            if result.minimal { " (minimal)" } else { "" }
+        );
+        return Ok(());
+    }
+
+    if !cranes.is_empty() {
+        for crane in &cranes {
+            let mut stacks = start_stacks.clone();
+            execute_all(crane.as_ref(), &mut stacks, &operations, mode).map_err(invalid_data)?;
+            println!("{}: {}", crane.name(), top_of_stacks(&stacks));
+        }
+        return Ok(());
+    }
 
     let mut stacks_part1 = start_stacks.clone();
-    let mut stacks_part2 = start_stacks.clone();
+    let mut stacks_part2 = start_stacks;
 
-    let path = Path::new("./bin/day5/input");
-    let file = File::open(&path)?;
-    let lines = BufReader::new(&file).lines().skip(10);
+    for (i, op) in operations.iter().enumerate() {
+        execute(&CrateMover9000, &mut stacks_part1, i + 1, op, mode).map_err(invalid_data)?;
+        execute(&CrateMover9001, &mut stacks_part2, i + 1, op, mode).map_err(invalid_data)?;
 
-    for l in lines {
-        let line = l.unwrap();
-        let instr = line.parse::<Instruction>().unwrap();
-        process_instructions_part1(&mut stacks_part1, &instr);
-        process_instructions_part2(&mut stacks_part2, &instr);
+        if dump {
+            println!("{}", op);
+            println!("Part 1\n{}", render_stacks(&stacks_part1, &labels));
+            println!("Part 2\n{}\n", render_stacks(&stacks_part2, &labels));
+        }
     }
 
     println!("Part 1");
@@ -109,6 +379,106 @@ mod tests {
         assert_eq!(i.to, 4);
     }
 
+    #[test]
+    fn test_parse_instruction_errors() {
+        assert_eq!(
+            "move 1 from 8".parse::<Instruction>().unwrap_err(),
+            "Expected 'move <amount> from <stack> to <stack>', found 'move 1 from 8'"
+        );
+        assert_eq!(
+            "swap 1 2".parse::<Instruction>().unwrap_err(),
+            "Expected a move instruction, found 'swap 1 2'"
+        );
+        assert_eq!(
+            "move 1 from a to 2".parse::<Instruction>().unwrap_err(),
+            "Unknown stack 'a'"
+        );
+    }
+
+    #[test]
+    fn test_display_instruction_round_trip() {
+        let i: Instruction = "move 12 from 8 to 4".parse().unwrap();
+        assert_eq!(i.to_string(), "move 12 from 8 to 4");
+        assert_eq!(i.to_string().parse::<Instruction>().unwrap(), i);
+    }
+
+    const DRAWING: &str = "    [D]    
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 ";
+
+    #[test]
+    fn test_parse_stacks() {
+        let lines: Vec<String> = DRAWING.lines().map(String::from).collect();
+        let stacks = parse_stacks(&lines).unwrap();
+        assert_eq!(stacks, example().0);
+    }
+
+    #[test]
+    fn test_parse_stacks_trimmed_rows() {
+        let lines: Vec<String> = vec!["    [D]", "[N] [C]", "[Z] [M] [P] [Q]", " 1   2   3   4"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let stacks = parse_stacks(&lines).unwrap();
+        assert_eq!(
+            stacks,
+            vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P'], vec!['Q']]
+        );
+    }
+
+    #[test]
+    fn test_parse_stacks_errors() {
+        let parse = |s: &str| parse_stacks(&s.lines().map(String::from).collect::<Vec<_>>());
+        assert!(parse("").is_err());
+        assert!(parse("[A] [B] [C]\n 1   2 ").is_err());
+        assert!(parse("[A] {B}\n 1   2 ").is_err());
+        assert!(parse("[A]    \n    [B]\n 1   2 ").is_err());
+        assert!(parse("[A] [B]\n 1   3 ").is_err());
+        assert!(parse("[A] [B]\n a   a ").is_err());
+    }
+
+    #[test]
+    fn test_parse_labels() {
+        assert_eq!(parse_labels(" 1   2   3 ").unwrap(), vec!["1", "2", "3"]);
+        assert_eq!(parse_labels(" a   b   3 ").unwrap(), vec!["a", "b", "3"]);
+        assert!(parse_labels(" a   1 ").is_err());
+        assert!(parse_labels(" a   a ").is_err());
+    }
+
+    #[test]
+    fn test_render_stacks() {
+        let labels = parse_labels(" 1   2   3 ").unwrap();
+        assert_eq!(render_stacks(&example().0, &labels), DRAWING);
+    }
+
+    #[test]
+    fn test_render_stacks_round_trip() {
+        let stacks = vec![vec![], vec!['A', 'B', 'C', 'D'], vec!['E'], vec![]];
+        let labels = parse_labels(" 1   2   3   4 ").unwrap();
+        let lines: Vec<String> = render_stacks(&stacks, &labels)
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(parse_stacks(&lines).unwrap(), stacks);
+    }
+
+    #[test]
+    fn test_render_stacks_named_round_trip() {
+        let drawing = "[A]     [C]\n[B] [D] [E]\n a  dock  c ";
+        let lines: Vec<String> = drawing.lines().map(String::from).collect();
+        let stacks = parse_stacks(&lines).unwrap();
+        let labels = parse_labels(lines.last().unwrap()).unwrap();
+
+        let rendered: Vec<String> = render_stacks(&stacks, &labels)
+            .lines()
+            .map(String::from)
+            .collect();
+        assert_eq!(rendered.last().unwrap(), " a  dock  c ");
+        assert_eq!(parse_stacks(&rendered).unwrap(), stacks);
+        assert_eq!(parse_labels(rendered.last().unwrap()).unwrap(), labels);
+    }
+
     #[test]
     fn test_instruction_processing_part1() {
         let mut stacks: Vec<Vec<char>> = vec![vec!['Z', 'N'], vec!['M', 'C', 'D'], vec!['P']];
@@ -0,0 +1,211 @@
+use std::{fmt, str::FromStr};
+
+use crate::Instruction;
+
+// A line of the instruction file. Stacks are referred to by number or by the
+// name used for them in the footer of the drawing, and resolved to numbers when parsed.
+//
+//   move 3 from 1 to 2   moves crates using the crane
+//   move 3 from a to b   the same using stack names
+//   swap 1 2             swaps two whole stacks
+//   reverse 1            reverses the order of the crates in a stack
+//   # ...                comment, until the end of the line
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    Move(Instruction),
+    Swap(u32, u32),
+    Reverse(u32),
+}
+
+fn parse_amount(token: &str) -> Result<u32, String> {
+    token
+        .parse::<u32>()
+        .map_err(|e| format!("Invalid amount '{}': {}", token, e))
+}
+
+fn parse_stack(token: &str, labels: &[String]) -> Result<u32, String> {
+    if token.chars().all(|c| c.is_ascii_digit()) {
+        return token
+            .parse::<u32>()
+            .map_err(|e| format!("Invalid stack number '{}': {}", token, e));
+    }
+
+    labels
+        .iter()
+        .position(|l| l == token)
+        .map(|i| i as u32 + 1)
+        .ok_or_else(|| format!("Unknown stack '{}'", token))
+}
+
+impl Operation {
+    // Parses a line using `labels` to resolve stack names. Returns `None` for
+    // lines without an operation, i.e. blank lines and comments.
+    pub fn parse(line: &str, labels: &[String]) -> Result<Option<Self>, String> {
+        let code = line.split('#').next().unwrap_or_default();
+        let tokens: Vec<&str> = code.split_whitespace().collect();
+
+        let op = match tokens.as_slice() {
+            [] => return Ok(None),
+            ["move", amount, "from", from, "to", to] => Operation::Move(Instruction {
+                amount: parse_amount(amount)?,
+                from: parse_stack(from, labels)?,
+                to: parse_stack(to, labels)?,
+            }),
+            ["swap", a, b] => Operation::Swap(parse_stack(a, labels)?, parse_stack(b, labels)?),
+            ["reverse", s] => Operation::Reverse(parse_stack(s, labels)?),
+            ["move", ..] => {
+                return Err(format!(
+                    "Expected 'move <amount> from <stack> to <stack>', found '{}'",
+                    code.trim()
+                ))
+            }
+            ["swap", ..] => {
+                return Err(format!(
+                    "Expected 'swap <stack> <stack>', found '{}'",
+                    code.trim()
+                ))
+            }
+            ["reverse", ..] => {
+                return Err(format!(
+                    "Expected 'reverse <stack>', found '{}'",
+                    code.trim()
+                ))
+            }
+            [op, ..] => return Err(format!("Unknown operation '{}'", op)),
+        };
+
+        Ok(Some(op))
+    }
+}
+
+impl FromStr for Operation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Operation::parse(s, &[])?.ok_or_else(|| String::from("Missing operation"))
+    }
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Move(instr) => write!(f, "{}", instr),
+            Operation::Swap(a, b) => write!(f, "swap {} {}", a, b),
+            Operation::Reverse(s) => write!(f, "reverse {}", s),
+        }
+    }
+}
+
+// Parses the instruction part of the input, `first_line` is the line number of
+// `lines[0]` used in error messages
+pub fn parse_operations(
+    lines: &[String],
+    labels: &[String],
+    first_line: usize,
+) -> Result<Vec<Operation>, String> {
+    let mut operations = vec![];
+    for (i, line) in lines.iter().enumerate() {
+        let op = Operation::parse(line, labels)
+            .map_err(|e| format!("Line {}: {}", first_line + i, e))?;
+        operations.extend(op);
+    }
+    Ok(operations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Vec<String> {
+        vec![
+            String::from("left"),
+            String::from("mid"),
+            String::from("right"),
+        ]
+    }
+
+    #[test]
+    fn test_parse_operations() {
+        let labels = labels();
+        let parse = |s: &str| Operation::parse(s, &labels);
+
+        assert_eq!(
+            parse("move 3 from left to right"),
+            Ok(Some(Operation::Move(Instruction {
+                amount: 3,
+                from: 1,
+                to: 3
+            })))
+        );
+        assert_eq!(
+            parse("  move 1 from 2 to mid  # back again"),
+            Ok(Some(Operation::Move(Instruction {
+                amount: 1,
+                from: 2,
+                to: 2
+            })))
+        );
+        assert_eq!(parse("swap left 3"), Ok(Some(Operation::Swap(1, 3))));
+        assert_eq!(parse("reverse mid"), Ok(Some(Operation::Reverse(2))));
+        assert_eq!(parse("# only a comment"), Ok(None));
+        assert_eq!(parse("   "), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let labels = labels();
+        let parse = |s: &str| Operation::parse(s, &labels).unwrap_err();
+
+        assert_eq!(
+            parse("move 1 from 2"),
+            "Expected 'move <amount> from <stack> to <stack>', found 'move 1 from 2'"
+        );
+        assert_eq!(
+            parse("move 1 from 2 to 3 and 4"),
+            "Expected 'move <amount> from <stack> to <stack>', found 'move 1 from 2 to 3 and 4'"
+        );
+        assert_eq!(
+            parse("move x from 1 to 2").split(':').next(),
+            Some("Invalid amount 'x'")
+        );
+        assert_eq!(parse("move 1 from top to 2"), "Unknown stack 'top'");
+        assert_eq!(
+            parse("swap 1"),
+            "Expected 'swap <stack> <stack>', found 'swap 1'"
+        );
+        assert_eq!(
+            parse("reverse 1 2"),
+            "Expected 'reverse <stack>', found 'reverse 1 2'"
+        );
+        assert_eq!(parse("lift 1"), "Unknown operation 'lift'");
+    }
+
+    #[test]
+    fn test_parse_operations_reports_line() {
+        let lines: Vec<String> = ["move 1 from 1 to 2", "# comment", "", "swap 1 x"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(
+            parse_operations(&lines, &[], 11),
+            Err(String::from("Line 14: Unknown stack 'x'"))
+        );
+        assert_eq!(parse_operations(&lines[..3], &[], 11).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let labels = labels();
+        for line in [
+            "move 3 from left to right",
+            "move 12 from 1 to 3",
+            "swap mid right",
+            "reverse 2 # comment",
+        ] {
+            let op = Operation::parse(line, &labels).unwrap().unwrap();
+            assert_eq!(op.to_string().parse::<Operation>(), Ok(op));
+        }
+        assert_eq!(Operation::Swap(2, 3).to_string(), "swap 2 3");
+        assert_eq!(Operation::Reverse(2).to_string(), "reverse 2");
+    }
+}
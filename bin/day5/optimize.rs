@@ -0,0 +1,260 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    crane::{check_instruction, execute, inverse, Crane, Mode},
+    operation::Operation,
+    Instruction,
+};
+
+type Stacks = Vec<Vec<char>>;
+
+pub struct Optimization {
+    pub instructions: Vec<Instruction>,
+    // Whether the instructions are known to be the shortest possible sequence
+    pub minimal: bool,
+}
+
+fn end_state(
+    crane: &dyn Crane,
+    start: &[Vec<char>],
+    instructions: &[Instruction],
+) -> Option<Stacks> {
+    let mut stacks = start.to_vec();
+    for instr in instructions {
+        check_instruction(&stacks, instr).ok()?;
+        crane.execute(&mut stacks, instr);
+    }
+    Some(stacks)
+}
+
+// Candidate rewrites of the instructions, each one instruction shorter or more
+fn rewrites(instructions: &[Instruction]) -> Vec<Vec<Instruction>> {
+    let mut candidates = vec![];
+
+    for i in 0..instructions.len() {
+        // Pairs of moves that cancel each other out
+        let inv = inverse(&instructions[i]);
+        if let Some(j) = instructions[i + 1..].iter().position(|o| *o == inv) {
+            let mut c = instructions.to_vec();
+            c.remove(i + 1 + j);
+            c.remove(i);
+            candidates.push(c);
+        }
+
+        // Consecutive moves between the same stacks
+        if let Some(next) = instructions.get(i + 1) {
+            if next.from == instructions[i].from && next.to == instructions[i].to {
+                let mut c = instructions.to_vec();
+                c[i].amount += next.amount;
+                c.remove(i + 1);
+                candidates.push(c);
+            }
+        }
+
+        // Moves that do not contribute to the end state at all
+        let mut c = instructions.to_vec();
+        c.remove(i);
+        candidates.push(c);
+    }
+
+    candidates
+}
+
+// Greedily applies rewrites for as long as they keep producing the same end state
+pub fn simplify(
+    crane: &dyn Crane,
+    start: &[Vec<char>],
+    instructions: &[Instruction],
+) -> Result<Vec<Instruction>, String> {
+    let mut stacks = start.to_vec();
+    for (i, instr) in instructions.iter().enumerate() {
+        let op = Operation::Move(instr.clone());
+        execute(crane, &mut stacks, i + 1, &op, Mode::Strict)?;
+    }
+    let end = stacks;
+
+    let mut current = instructions.to_vec();
+    while let Some(shorter) = rewrites(&current)
+        .into_iter()
+        .find(|c| end_state(crane, start, c).as_ref() == Some(&end))
+    {
+        current = shorter;
+    }
+
+    Ok(current)
+}
+
+// Breadth first search for the shortest instruction sequence turning `start`
+// into `end`. Gives up with `None` after visiting `max_states` states.
+pub fn shortest(
+    crane: &dyn Crane,
+    start: &[Vec<char>],
+    end: &[Vec<char>],
+    max_states: usize,
+) -> Option<Vec<Instruction>> {
+    // Each visited state with the state and instruction it was reached from
+    let mut previous: HashMap<Stacks, Option<(Stacks, Instruction)>> = HashMap::new();
+    let mut queue: VecDeque<Stacks> = VecDeque::new();
+    previous.insert(start.to_vec(), None);
+    queue.push_back(start.to_vec());
+
+    while let Some(stacks) = queue.pop_front() {
+        if stacks == end {
+            let mut path = vec![];
+            let mut state = &stacks;
+            while let Some(Some((prev, instr))) = previous.get(state) {
+                path.push(instr.clone());
+                state = prev;
+            }
+            path.reverse();
+            return Some(path);
+        }
+
+        for from in 1..=stacks.len() as u32 {
+            for to in (1..=stacks.len() as u32).filter(|to| *to != from) {
+                for amount in 1..=stacks[from as usize - 1].len() as u32 {
+                    let instr = Instruction { amount, from, to };
+                    if check_instruction(&stacks, &instr).is_err() {
+                        continue;
+                    }
+
+                    let mut next = stacks.clone();
+                    crane.execute(&mut next, &instr);
+                    if previous.contains_key(&next) {
+                        continue;
+                    }
+                    if previous.len() >= max_states {
+                        return None;
+                    }
+
+                    previous.insert(next.clone(), Some((stacks.clone(), instr)));
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// Finds a sequence of instructions producing the same end state as `instructions`,
+// searching exhaustively when the state space is small enough
+pub fn optimize(
+    crane: &dyn Crane,
+    start: &[Vec<char>],
+    instructions: &[Instruction],
+    max_states: usize,
+) -> Result<Optimization, String> {
+    let simplified = simplify(crane, start, instructions)?;
+    let end = end_state(crane, start, &simplified).expect("simplified instructions are valid");
+
+    match shortest(crane, start, &end, max_states) {
+        Some(shortest) if shortest.len() <= simplified.len() => Ok(Optimization {
+            instructions: shortest,
+            minimal: true,
+        }),
+        _ => Ok(Optimization {
+            instructions: simplified,
+            minimal: false,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        crane::{CrateMover9000, CrateMover9001},
+        example,
+    };
+
+    fn parse(instructions: &[&str]) -> Vec<Instruction> {
+        instructions.iter().map(|i| i.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_simplify_removes_cancelling_moves() {
+        let start = vec![vec!['A', 'B'], vec!['C'], vec![]];
+        let instructions = parse(&[
+            "move 1 from 1 to 2",
+            "move 0 from 1 to 3",
+            "move 2 from 2 to 3",
+            "move 2 from 3 to 2",
+            "move 1 from 2 to 1",
+        ]);
+
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            assert!(simplify(crane, &start, &instructions).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_simplify_merges_single_moves() {
+        let start = vec![vec!['A', 'B', 'C'], vec![]];
+        let instructions = parse(&["move 1 from 1 to 2", "move 2 from 1 to 2"]);
+
+        let simplified = simplify(&CrateMover9000, &start, &instructions).unwrap();
+        assert_eq!(simplified, parse(&["move 3 from 1 to 2"]));
+
+        // The CrateMover 9001 keeps the order within each move, so the moves differ
+        let simplified = simplify(&CrateMover9001, &start, &instructions).unwrap();
+        assert_eq!(simplified, instructions);
+    }
+
+    #[test]
+    fn test_simplify_rejects_invalid_instructions() {
+        let start = vec![vec!['A'], vec![]];
+        let instructions = parse(&["move 2 from 1 to 2"]);
+        assert!(simplify(&CrateMover9000, &start, &instructions).is_err());
+    }
+
+    #[test]
+    fn test_optimize_example() {
+        let (start, operations) = example();
+        let instructions: Vec<Instruction> = operations
+            .iter()
+            .map(|op| op.to_string().parse().unwrap())
+            .collect();
+
+        for crane in [&CrateMover9000 as &dyn Crane, &CrateMover9001] {
+            let result = optimize(crane, &start, &instructions, 100_000).unwrap();
+            assert!(result.minimal);
+            assert!(result.instructions.len() <= instructions.len());
+            assert_eq!(
+                end_state(crane, &start, &result.instructions),
+                end_state(crane, &start, &instructions)
+            );
+        }
+    }
+
+    #[test]
+    fn test_optimize_finds_shorter_sequence() {
+        // Moving two crates one by one via the middle stack is the same as moving
+        // them directly with the CrateMover 9000
+        let start = vec![vec!['A', 'B'], vec![], vec![]];
+        let instructions = parse(&[
+            "move 1 from 1 to 2",
+            "move 1 from 2 to 3",
+            "move 1 from 1 to 2",
+            "move 1 from 2 to 3",
+        ]);
+
+        let result = optimize(&CrateMover9001, &start, &instructions, 100_000).unwrap();
+        assert!(result.minimal);
+        assert_eq!(result.instructions.len(), 2);
+
+        let result = optimize(&CrateMover9000, &start, &instructions, 100_000).unwrap();
+        assert!(result.minimal);
+        assert_eq!(result.instructions, parse(&["move 2 from 1 to 3"]));
+    }
+
+    #[test]
+    fn test_optimize_falls_back_when_search_is_too_large() {
+        let start = vec![vec!['A', 'B', 'C'], vec![], vec![]];
+        let instructions = parse(&["move 1 from 1 to 2", "move 1 from 1 to 3"]);
+
+        let result = optimize(&CrateMover9000, &start, &instructions, 1).unwrap();
+        assert!(!result.minimal);
+        assert_eq!(result.instructions, instructions);
+    }
+}
@@ -0,0 +1,37 @@
+use std::{hint::black_box, time::Instant};
+
+use crate::{
+    detect_marker_bitset, detect_marker_bytes, detect_marker_chars, detect_sequence_marker_naive,
+    is_lowercase_letters,
+};
+
+const RUNS: u32 = 1000;
+
+type Detector = fn(&str, usize) -> Option<usize>;
+
+// Times every marker detector on `input` for both window sizes,
+// e.g. `cargo run --release --bin day6 -- --bench`
+pub fn run(input: &str) {
+    let mut detectors: Vec<(&str, Detector)> = vec![
+        ("is_chars_unique", detect_sequence_marker_naive),
+        ("sliding window (chars)", detect_marker_chars),
+        ("sliding window (bytes)", |s, n| {
+            detect_marker_bytes(s.as_bytes(), n)
+        }),
+    ];
+    if is_lowercase_letters(input.as_bytes()) {
+        detectors.push(("bitset", |s, n| detect_marker_bitset(s.as_bytes(), n)));
+    }
+
+    for distinct_num in [4, 14] {
+        println!("{} distinct chars, {} runs", distinct_num, RUNS);
+        for (name, detect) in &detectors {
+            let start = Instant::now();
+            for _ in 0..RUNS {
+                black_box(detect(black_box(input), distinct_num));
+            }
+            let elapsed = start.elapsed() / RUNS;
+            println!("  {:<24}{:>10.1?} per run", name, elapsed);
+        }
+    }
+}
@@ -0,0 +1,214 @@
+use std::io::{self, Read};
+
+use crate::stream::{Marker, MarkerKind, MarkerStream};
+
+// The signal between a marker and the start of the next marker of the same kind,
+// or the end of the signal. Offsets are in signal bytes, `end` is exclusive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub kind: MarkerKind,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Frame {
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FrameStats {
+    pub count: usize,
+    pub total_len: usize,
+    pub longest: usize,
+}
+
+impl FrameStats {
+    fn add(&mut self, frame: &Frame) {
+        self.count += 1;
+        self.total_len += frame.len();
+        self.longest = self.longest.max(frame.len());
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Stats {
+    pub packets: FrameStats,
+    pub messages: FrameStats,
+    // Longest distance between two consecutive markers of any kind
+    pub longest_gap: usize,
+}
+
+// Splits a signal into packet and message frames. Frames are yielded once the
+// next marker of the same kind, or the end of the signal, has been seen.
+pub struct Decoder<R: Read> {
+    markers: MarkerStream<R>,
+    open_packet: Option<Marker>,
+    open_message: Option<Marker>,
+    last_marker: Option<usize>,
+    // Frames closed at the end of the signal, waiting to be yielded
+    closing: Vec<Frame>,
+    finished: bool,
+    stats: Stats,
+}
+
+impl<R: Read> Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            markers: MarkerStream::new(reader),
+            open_packet: None,
+            open_message: None,
+            last_marker: None,
+            closing: vec![],
+            finished: false,
+            stats: Stats::default(),
+        }
+    }
+
+    // Statistics of the frames yielded so far
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    fn close(&mut self, open: Marker, end: usize) -> Frame {
+        let frame = Frame {
+            kind: open.kind,
+            start: open.position,
+            end: end.max(open.position),
+        };
+        match frame.kind {
+            MarkerKind::StartOfPacket => self.stats.packets.add(&frame),
+            MarkerKind::StartOfMessage => self.stats.messages.add(&frame),
+        }
+        frame
+    }
+}
+
+impl<R: Read> Iterator for Decoder<R> {
+    type Item = io::Result<Frame>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while !self.finished {
+            let marker = match self.markers.next() {
+                Some(Ok(marker)) => marker,
+                Some(Err(e)) => return Some(Err(e)),
+                None => {
+                    // Close the frames still open in the order they started
+                    self.finished = true;
+                    let end = self.markers.position();
+                    let mut open: Vec<Marker> = [self.open_packet.take(), self.open_message.take()]
+                        .into_iter()
+                        .flatten()
+                        .collect();
+                    open.sort_by_key(|m| std::cmp::Reverse(m.position));
+                    self.closing = open.into_iter().map(|m| self.close(m, end)).collect();
+                    break;
+                }
+            };
+
+            if let Some(last) = self.last_marker {
+                self.stats.longest_gap = self.stats.longest_gap.max(marker.position - last);
+            }
+            self.last_marker = Some(marker.position);
+
+            let open = match marker.kind {
+                MarkerKind::StartOfPacket => self.open_packet.replace(marker),
+                MarkerKind::StartOfMessage => self.open_message.replace(marker),
+            };
+            if let Some(open) = open {
+                let end = marker.position - marker.kind.distinct_num();
+                return Some(Ok(self.close(open, end)));
+            }
+        }
+
+        self.closing.pop().map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(signal: &str) -> (Vec<Frame>, Stats) {
+        let mut decoder = Decoder::new(signal.as_bytes());
+        let frames = decoder
+            .by_ref()
+            .collect::<io::Result<Vec<Frame>>>()
+            .unwrap();
+        (frames, decoder.stats().clone())
+    }
+
+    fn packet(start: usize, end: usize) -> Frame {
+        Frame {
+            kind: MarkerKind::StartOfPacket,
+            start,
+            end,
+        }
+    }
+
+    fn message(start: usize, end: usize) -> Frame {
+        Frame {
+            kind: MarkerKind::StartOfMessage,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn test_decode_packets() {
+        let signal = "abcdddaaabcdddeeefghhh";
+        let (frames, stats) = decode(signal);
+
+        assert_eq!(frames, vec![packet(4, 8), packet(12, 16), packet(20, 22)]);
+        assert_eq!(&signal[frames[0].start..frames[0].end], "ddaa");
+        assert_eq!(&signal[frames[2].start..frames[2].end], "hh");
+
+        assert_eq!(
+            stats.packets,
+            FrameStats {
+                count: 3,
+                total_len: 10,
+                longest: 4
+            }
+        );
+        assert_eq!(stats.messages, FrameStats::default());
+        assert_eq!(stats.longest_gap, 8);
+    }
+
+    #[test]
+    fn test_decode_packets_and_messages() {
+        let signal = "abcdefghijklmnooabcdefghijklmnpp";
+        let (frames, stats) = decode(signal);
+
+        assert_eq!(
+            frames,
+            vec![
+                packet(4, 15),
+                message(14, 15),
+                packet(19, 32),
+                message(29, 32)
+            ]
+        );
+        assert_eq!(stats.packets.count, 2);
+        assert_eq!(stats.packets.longest, 13);
+        assert_eq!(stats.messages.count, 2);
+        assert_eq!(stats.messages.total_len, 4);
+        assert_eq!(stats.longest_gap, 10);
+    }
+
+    #[test]
+    fn test_decode_without_markers() {
+        let (frames, stats) = decode("aaaaaaaa");
+        assert!(frames.is_empty());
+        assert_eq!(stats, Stats::default());
+    }
+
+    #[test]
+    fn test_decode_overlapping_markers() {
+        // The second marker's window 3..7 starts before the first marker ends at
+        // 4, so the frame between them is empty
+        let (frames, _) = decode("abcdcab");
+        assert_eq!(frames, vec![packet(4, 4), packet(7, 7)]);
+    }
+}
@@ -1,7 +1,11 @@
+mod bench;
+mod decoder;
+mod stream;
+
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     fs::File,
-    io::{self, BufRead, BufReader},
+    io::{self, BufRead, BufReader, BufWriter, Write},
     path::Path,
 };
 
@@ -14,29 +18,229 @@ fn is_chars_unique(buf: Vec<char>) -> bool {
     buf.len() == freq.len()
 }
 
-fn detect_sequence_marker(input: &String, distinct_num: usize) -> u32 {
-    for i in 0..input.len() - distinct_num - 1 {
-        let ss = &input[i..i + distinct_num];
-        if is_chars_unique(ss.chars().collect()) {
-            return (i + distinct_num) as u32;
+fn detect_sequence_marker_naive(input: &str, distinct_num: usize) -> Option<usize> {
+    if distinct_num == 0 {
+        return Some(0);
+    }
+
+    let chars: Vec<char> = input.chars().collect();
+    for (i, window) in chars.windows(distinct_num).enumerate() {
+        if is_chars_unique(window.to_vec()) {
+            return Some(i + distinct_num);
+        }
+    }
+
+    None
+}
+
+// Number of occurrences of each symbol in the current window
+trait SymbolCounts<T> {
+    // Returns the count after adding the symbol
+    fn add(&mut self, symbol: T) -> usize;
+    // Returns the count after removing the symbol
+    fn remove(&mut self, symbol: T) -> usize;
+}
+
+impl SymbolCounts<u8> for [usize; 256] {
+    fn add(&mut self, symbol: u8) -> usize {
+        self[symbol as usize] += 1;
+        self[symbol as usize]
+    }
+
+    fn remove(&mut self, symbol: u8) -> usize {
+        self[symbol as usize] -= 1;
+        self[symbol as usize]
+    }
+}
+
+impl SymbolCounts<char> for HashMap<char, usize> {
+    fn add(&mut self, symbol: char) -> usize {
+        let count = self.entry(symbol).or_insert(0);
+        *count += 1;
+        *count
+    }
+
+    fn remove(&mut self, symbol: char) -> usize {
+        let count = self.entry(symbol).or_insert(0);
+        *count -= 1;
+        *count
+    }
+}
+
+// Slides a window of `distinct_num` symbols over the input, keeping track of how
+// many symbols occur more than once in it. Returns the number of symbols read
+// when the first window without repeated symbols ends.
+fn detect_unique_window<T, C>(symbols: &[T], distinct_num: usize, counts: &mut C) -> Option<usize>
+where
+    T: Copy,
+    C: SymbolCounts<T>,
+{
+    if distinct_num == 0 {
+        return Some(0);
+    }
+
+    let mut repeated = 0;
+    for (i, symbol) in symbols.iter().enumerate() {
+        if counts.add(*symbol) == 2 {
+            repeated += 1;
+        }
+        if i >= distinct_num && counts.remove(symbols[i - distinct_num]) == 1 {
+            repeated -= 1;
+        }
+        if i + 1 >= distinct_num && repeated == 0 {
+            return Some(i + 1);
+        }
+    }
+
+    None
+}
+
+fn detect_marker_bytes(input: &[u8], distinct_num: usize) -> Option<usize> {
+    detect_unique_window(input, distinct_num, &mut [0; 256])
+}
+
+// Same as `detect_marker_bytes`, but for any Unicode input with the position counted in chars
+fn detect_marker_chars(input: &str, distinct_num: usize) -> Option<usize> {
+    let chars: Vec<char> = input.chars().collect();
+    detect_unique_window(&chars, distinct_num, &mut HashMap::new())
+}
+
+fn is_lowercase_letters(input: &[u8]) -> bool {
+    input.iter().all(u8::is_ascii_lowercase)
+}
+
+// Same as `detect_marker_bytes` for input made of lowercase ASCII letters only.
+// Each letter gets a bit of a 32-bit mask and the masks of the window are XORed
+// together, so a letter's bit is set when it occurs an odd number of times. The
+// window has no repeated letters exactly when all `distinct_num` bits are set.
+fn detect_marker_bitset(input: &[u8], distinct_num: usize) -> Option<usize> {
+    debug_assert!(is_lowercase_letters(input));
+    if distinct_num == 0 {
+        return Some(0);
+    }
+
+    if input.len() < distinct_num {
+        return None;
+    }
+
+    let bit = |b: &u8| 1u32 << (b % 32);
+    let mut mask = input[..distinct_num]
+        .iter()
+        .fold(0, |mask, b| mask ^ bit(b));
+    if mask.count_ones() as usize == distinct_num {
+        return Some(distinct_num);
+    }
+    for (i, (new, old)) in input[distinct_num..].iter().zip(input).enumerate() {
+        mask ^= bit(new) ^ bit(old);
+        if mask.count_ones() as usize == distinct_num {
+            return Some(i + distinct_num + 1);
         }
     }
 
-    0
+    None
+}
+
+// Returns the number of chars read when the first `distinct_num` distinct chars
+// in a row have been seen, or `None` if there are no such chars. Uses the byte
+// sliding window for ASCII input, which `--bench` shows to be the fastest: the
+// bitset is no faster and would need another pass to check for lowercase input.
+fn detect_sequence_marker(input: &str, distinct_num: usize) -> Option<usize> {
+    if input.is_ascii() {
+        detect_marker_bytes(input.as_bytes(), distinct_num)
+    } else {
+        detect_marker_chars(input, distinct_num)
+    }
+}
+
+fn print_marker(part: u32, marker: Option<usize>) {
+    match marker {
+        Some(marker) => println!("Part {} - marker: {}", part, marker),
+        None => println!("Part {} - no marker found", part),
+    }
+}
+
+fn kind_name(kind: stream::MarkerKind) -> &'static str {
+    match kind {
+        stream::MarkerKind::StartOfPacket => "start-of-packet",
+        stream::MarkerKind::StartOfMessage => "start-of-message",
+    }
+}
+
+// Prints every marker of a signal piped through stdin, e.g.
+// `cat capture | cargo run --bin day6 -- --stream`
+fn stream_markers() -> io::Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    for marker in stream::MarkerStream::new(io::stdin().lock()) {
+        let marker = marker?;
+        writeln!(
+            out,
+            "{} marker: {}",
+            kind_name(marker.kind),
+            marker.position
+        )?;
+    }
+
+    out.flush()
+}
+
+// Prints the packets and messages of a signal piped through stdin, followed by
+// statistics, e.g. `cat capture | cargo run --bin day6 -- --decode`
+fn decode_frames() -> io::Result<()> {
+    let mut out = BufWriter::new(io::stdout().lock());
+    let mut decoder = decoder::Decoder::new(io::stdin().lock());
+    for frame in decoder.by_ref() {
+        let frame = frame?;
+        writeln!(
+            out,
+            "{} frame: {}..{} ({} bytes)",
+            kind_name(frame.kind),
+            frame.start,
+            frame.end,
+            frame.len()
+        )?;
+    }
+
+    let stats = decoder.stats();
+    for (kind, frames) in [("Packets", &stats.packets), ("Messages", &stats.messages)] {
+        writeln!(
+            out,
+            "{}: {}, total length {}, longest {}",
+            kind, frames.count, frames.total_len, frames.longest
+        )?;
+    }
+    writeln!(out, "Longest gap between markers: {}", stats.longest_gap)?;
+
+    out.flush()
 }
 
 fn main() -> io::Result<()> {
+    if std::env::args().any(|a| a == "--stream") {
+        return stream_markers();
+    }
+    if std::env::args().any(|a| a == "--decode") {
+        return decode_frames();
+    }
+
     let path = Path::new("./bin/day6/input");
-    let file = File::open(&path)?;
+    let file = File::open(path)?;
     let lines = BufReader::new(&file).lines();
 
+    if std::env::args().any(|a| a == "--bench") {
+        for line in lines {
+            bench::run(&line?);
+        }
+        return Ok(());
+    }
+
     for l in lines {
         let line = l.unwrap();
         let marker_4 = detect_sequence_marker(&line, 4);
-        println!("Part 1 - marker: {}", marker_4);
+        debug_assert_eq!(marker_4, detect_sequence_marker_naive(&line, 4));
+        print_marker(1, marker_4);
 
         let marker_14 = detect_sequence_marker(&line, 14);
-        println!("Part 2 - marker: {}", marker_14);
+        debug_assert_eq!(marker_14, detect_sequence_marker_naive(&line, 14));
+        print_marker(2, marker_14);
     }
 
     Ok(())
@@ -80,7 +284,7 @@ mod tests {
     fn test_detect_sequence_finds_marker_0() {
         assert_eq!(
             detect_sequence_marker(&String::from("bvwbjplbgvbhsrlpgdmjqwftvncz"), 4),
-            5
+            Some(5)
         );
     }
 
@@ -88,7 +292,7 @@ mod tests {
     fn test_detect_sequence_finds_marker_1() {
         assert_eq!(
             detect_sequence_marker(&String::from("nppdvjthqldpwncqszvftbrmjlhg"), 4),
-            6
+            Some(6)
         );
     }
 
@@ -96,7 +300,7 @@ mod tests {
     fn test_detect_sequence_finds_marker_2() {
         assert_eq!(
             detect_sequence_marker(&String::from("nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg"), 4),
-            10
+            Some(10)
         );
     }
 
@@ -104,7 +308,177 @@ mod tests {
     fn test_detect_sequence_finds_marker_3() {
         assert_eq!(
             detect_sequence_marker(&String::from("zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw"), 4),
-            11
+            Some(11)
+        );
+    }
+
+    #[test]
+    fn test_detect_marker_bytes() {
+        assert_eq!(
+            detect_marker_bytes(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4),
+            Some(7)
+        );
+        assert_eq!(
+            detect_marker_bytes(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14),
+            Some(19)
+        );
+        assert_eq!(detect_marker_bytes(b"aaaa", 2), None);
+    }
+
+    #[test]
+    fn test_detect_marker_chars_unicode() {
+        assert_eq!(detect_marker_chars("ääöäüß", 3), Some(5));
+        assert_eq!(detect_sequence_marker("ääöäüß", 3), Some(5));
+        assert_eq!(detect_marker_chars("🎄🎄🎅🎄🦌⛄", 4), Some(6));
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_matches_naive() {
+        let input = "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsgzcfzfwzzqfrljwzlrfnpqdbhtmscgvjw";
+        for distinct_num in 1..=14 {
+            assert_eq!(
+                detect_sequence_marker(input, distinct_num),
+                detect_sequence_marker_naive(input, distinct_num)
+            );
+        }
+    }
+
+    #[test]
+    fn test_detect_marker_bitset() {
+        assert_eq!(
+            detect_marker_bitset(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 4),
+            Some(7)
+        );
+        assert_eq!(
+            detect_marker_bitset(b"mjqjpqmgbljsphdztnvjfqwrcgsmlb", 14),
+            Some(19)
+        );
+        // A letter occurring three times sets its bit again
+        assert_eq!(detect_marker_bitset(b"aaab", 2), Some(4));
+        assert_eq!(detect_marker_bitset(b"aaab", 4), None);
+        assert_eq!(
+            detect_marker_bitset(b"abcdefghijklmnopqrstuvwxyz", 27),
+            None
+        );
+        assert_eq!(
+            detect_marker_bitset(b"abcdefghijklmnopqrstuvwxyz", 26),
+            Some(26)
         );
     }
+
+    #[test]
+    fn test_detectors_agree() {
+        let inputs = [
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+            "bvwbjplbgvbhsrlpgdmjqwftvncz",
+            "nppdvjthqldpwncqszvftbrmjlhg",
+            "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg",
+            "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw",
+            "abcabcabcabc",
+            "aaab",
+            "",
+        ];
+        for input in inputs {
+            for distinct_num in 0..=15 {
+                let expected = detect_sequence_marker_naive(input, distinct_num);
+                assert_eq!(detect_marker_chars(input, distinct_num), expected);
+                assert_eq!(
+                    detect_marker_bytes(input.as_bytes(), distinct_num),
+                    expected
+                );
+                assert_eq!(
+                    detect_marker_bitset(input.as_bytes(), distinct_num),
+                    expected
+                );
+                assert_eq!(detect_sequence_marker(input, distinct_num), expected);
+            }
+        }
+    }
+
+    // xorshift64, enough to generate test inputs without external crates
+    struct Rng(u64);
+
+    impl Rng {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            (self.0 % n as u64) as usize
+        }
+    }
+
+    // Compares every pair of chars of every window
+    fn brute_force_marker(input: &[char], distinct_num: usize) -> Option<usize> {
+        (distinct_num..=input.len()).find(|&end| {
+            let window = &input[end - distinct_num..end];
+            (0..window.len()).all(|i| (i + 1..window.len()).all(|j| window[i] != window[j]))
+        })
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_matches_brute_force() {
+        let alphabets = ["a", "ab", "abc", "abcdef", "abcdefghijklmnop", "xyzäöü🎄"];
+        let mut rng = Rng(0x2022_1206);
+        let (mut found, mut not_found, mut longer_than_input) = (0, 0, 0);
+
+        for _ in 0..5000 {
+            let alphabet: Vec<char> = alphabets[rng.below(alphabets.len())].chars().collect();
+            let len = rng.below(40);
+            let input: Vec<char> = (0..len)
+                .map(|_| alphabet[rng.below(alphabet.len())])
+                .collect();
+            let input_str: String = input.iter().collect();
+            let distinct_num = rng.below(18);
+
+            let expected = brute_force_marker(&input, distinct_num);
+            assert_eq!(
+                detect_sequence_marker(&input_str, distinct_num),
+                expected,
+                "input '{}', distinct_num {}",
+                input_str,
+                distinct_num
+            );
+
+            match expected {
+                Some(_) => found += 1,
+                None => not_found += 1,
+            }
+            if distinct_num > len {
+                longer_than_input += 1;
+            }
+        }
+
+        assert!(found > 0 && not_found > 0 && longer_than_input > 0);
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_in_last_window() {
+        assert_eq!(detect_sequence_marker("aaaabcd", 4), Some(7));
+        assert_eq!(detect_sequence_marker("aaabcd", 4), Some(6));
+        assert_eq!(detect_sequence_marker("abcd", 4), Some(4));
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_short_input() {
+        assert_eq!(detect_sequence_marker("", 4), None);
+        assert_eq!(detect_sequence_marker("abc", 4), None);
+        assert_eq!(detect_sequence_marker("äbc", 4), None);
+        assert_eq!(detect_sequence_marker_naive("abc", 4), None);
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_not_found() {
+        assert_eq!(detect_sequence_marker("abcabcabcabc", 4), None);
+        assert_eq!(detect_sequence_marker("aaaaaaa", 2), None);
+        assert_eq!(detect_sequence_marker_naive("abcabcabcabc", 4), None);
+    }
+
+    #[test]
+    fn test_detect_sequence_marker_small_windows() {
+        assert_eq!(detect_sequence_marker("", 0), Some(0));
+        assert_eq!(detect_sequence_marker("aab", 0), Some(0));
+        assert_eq!(detect_sequence_marker("aab", 1), Some(1));
+        assert_eq!(detect_sequence_marker("aab", 2), Some(3));
+        assert_eq!(detect_sequence_marker_naive("aab", 0), Some(0));
+    }
 }
@@ -0,0 +1,198 @@
+use std::{
+    collections::VecDeque,
+    io::{self, BufReader, Bytes, Read},
+};
+
+use crate::SymbolCounts;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkerKind {
+    StartOfPacket,
+    StartOfMessage,
+}
+
+impl MarkerKind {
+    pub fn distinct_num(&self) -> usize {
+        match self {
+            MarkerKind::StartOfPacket => 4,
+            MarkerKind::StartOfMessage => 14,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Marker {
+    pub kind: MarkerKind,
+    // Number of signal bytes read when the marker was complete
+    pub position: usize,
+}
+
+// The last `distinct_num` bytes of the signal
+struct Window {
+    distinct_num: usize,
+    bytes: VecDeque<u8>,
+    counts: [usize; 256],
+    repeated: usize,
+    unique: bool,
+}
+
+impl Window {
+    fn new(distinct_num: usize) -> Self {
+        Self {
+            distinct_num,
+            bytes: VecDeque::with_capacity(distinct_num + 1),
+            counts: [0; 256],
+            repeated: 0,
+            unique: false,
+        }
+    }
+
+    // Adds a byte, returns true if the window just became free of repeated bytes
+    fn push(&mut self, b: u8) -> bool {
+        self.bytes.push_back(b);
+        if self.counts.add(b) == 2 {
+            self.repeated += 1;
+        }
+        if self.bytes.len() > self.distinct_num {
+            let old = self.bytes.pop_front().unwrap();
+            if self.counts.remove(old) == 1 {
+                self.repeated -= 1;
+            }
+        }
+
+        let was_unique = self.unique;
+        self.unique = self.bytes.len() == self.distinct_num && self.repeated == 0;
+        self.unique && !was_unique
+    }
+}
+
+// Reports a marker every time the last 4 (start-of-packet) or 14 (start-of-message)
+// bytes of the signal become distinct, using constant memory. Line breaks are not
+// part of the signal and are skipped.
+pub struct MarkerStream<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    position: usize,
+    packet: Window,
+    message: Window,
+    pending: Option<Marker>,
+}
+
+impl<R: Read> MarkerStream<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            bytes: BufReader::new(reader).bytes(),
+            position: 0,
+            packet: Window::new(MarkerKind::StartOfPacket.distinct_num()),
+            message: Window::new(MarkerKind::StartOfMessage.distinct_num()),
+            pending: None,
+        }
+    }
+
+    // Number of signal bytes read so far
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl<R: Read> Iterator for MarkerStream<R> {
+    type Item = io::Result<Marker>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(marker) = self.pending.take() {
+            return Some(Ok(marker));
+        }
+
+        for b in self.bytes.by_ref() {
+            let b = match b {
+                Ok(b'\n' | b'\r') => continue,
+                Ok(b) => b,
+                Err(e) => return Some(Err(e)),
+            };
+            self.position += 1;
+
+            let packet = self.packet.push(b);
+            let message = self.message.push(b);
+            let marker = |kind| Marker {
+                kind,
+                position: self.position,
+            };
+
+            match (packet, message) {
+                (true, true) => {
+                    self.pending = Some(marker(MarkerKind::StartOfMessage));
+                    return Some(Ok(marker(MarkerKind::StartOfPacket)));
+                }
+                (true, false) => return Some(Ok(marker(MarkerKind::StartOfPacket))),
+                (false, true) => return Some(Ok(marker(MarkerKind::StartOfMessage))),
+                (false, false) => (),
+            }
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::detect_sequence_marker;
+
+    fn markers(input: &str) -> Vec<Marker> {
+        MarkerStream::new(input.as_bytes())
+            .collect::<io::Result<Vec<Marker>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_stream_first_markers_match_detector() {
+        for input in [
+            "mjqjpqmgbljsphdztnvjfqwrcgsmlb",
+            "bvwbjplbgvbhsrlpgdmjqwftvncz",
+            "nppdvjthqldpwncqszvftbrmjlhg",
+            "nznrnfrfntjfmvfwmzdfjlvtqnbhcprsg",
+            "zcfzfwzzqfrljwzlrfnpqdbhtmscgvjw",
+        ] {
+            let markers = markers(input);
+            for kind in [MarkerKind::StartOfPacket, MarkerKind::StartOfMessage] {
+                assert_eq!(
+                    markers.iter().find(|m| m.kind == kind).map(|m| m.position),
+                    detect_sequence_marker(input, kind.distinct_num())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_stream_reports_every_marker() {
+        let packet = |position| Marker {
+            kind: MarkerKind::StartOfPacket,
+            position,
+        };
+
+        assert_eq!(markers("abcdddabcdd"), vec![packet(4), packet(9)]);
+        assert_eq!(markers("aaaa"), vec![]);
+    }
+
+    #[test]
+    fn test_stream_reports_packet_and_message_markers() {
+        let markers = markers("abcdefghijklmnabcdefghijklmn");
+        assert_eq!(
+            markers,
+            vec![
+                Marker {
+                    kind: MarkerKind::StartOfPacket,
+                    position: 4
+                },
+                Marker {
+                    kind: MarkerKind::StartOfMessage,
+                    position: 14
+                }
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stream_skips_line_breaks() {
+        assert_eq!(markers("ab\r\ncd"), markers("abcd"));
+    }
+}
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use aoc22::tree::{NodeId, Tree};
+
+use crate::{is_node_dir, FileSystemNode};
+
+pub struct Disk {
+    pub total: usize,
+    pub required: usize,
+}
+
+impl Disk {
+    // Space that has to be freed when `used` is taken, 0 if enough is free already
+    pub fn space_needed(&self, used: usize) -> usize {
+        self.required
+            .saturating_sub(self.total.saturating_sub(used))
+    }
+}
+
+fn dirs(tree: &Tree<FileSystemNode>) -> impl Iterator<Item = NodeId> + '_ {
+    tree.ids().filter(|id| is_node_dir(tree.get(*id)))
+}
+
+// The smallest directory freeing at least `needed` when deleted
+pub fn smallest_dir(
+    tree: &Tree<FileSystemNode>,
+    sizes: &HashMap<NodeId, usize>,
+    needed: usize,
+) -> Option<NodeId> {
+    dirs(tree)
+        .filter(|d| sizes[d] >= needed)
+        .min_by_key(|d| sizes[d])
+}
+
+pub struct Cleanup {
+    pub dirs: Vec<NodeId>,
+    // Whether the directories are known to free the least space possible
+    pub minimal: bool,
+}
+
+// How a total is reached: by deleting nothing, a single directory, or the
+// directories of two other picks
+enum Pick {
+    Nothing,
+    Dir(NodeId),
+    Both(usize, usize),
+}
+
+// Sorts the totals and keeps one of each below `needed` and the smallest one
+// reaching it, as larger totals can never be part of a better plan
+fn prune<T>(mut totals: Vec<(usize, T)>, needed: usize) -> Vec<(usize, T)> {
+    totals.sort_unstable_by_key(|(size, _)| *size);
+    totals.dedup_by_key(|(size, _)| *size);
+    if let Some(enough) = totals.iter().position(|(size, _)| *size >= needed) {
+        totals.truncate(enough + 1);
+    }
+    totals
+}
+
+// Drops evenly spaced totals below `needed` until at most `max_choices` are left
+fn thin<T>(totals: Vec<(usize, T)>, needed: usize, max_choices: usize) -> Vec<(usize, T)> {
+    let step = totals.len().div_ceil(max_choices.max(1));
+    totals
+        .into_iter()
+        .enumerate()
+        .filter(|(i, (size, _))| *size >= needed || i % step == 0)
+        .map(|(_, total)| total)
+        .collect()
+}
+
+// Most totals tracked for a directory. Combining the totals of two directories
+// creates up to the square of them, so this bounds memory to about 100 MB.
+pub const MAX_CHOICES: usize = 2048;
+
+// The directories, none of them inside another, freeing at least `needed` with
+// the smallest total size. Finding them is a subset sum problem, so when more
+// than `max_choices` totals would have to be tracked for a directory only some
+// of them are kept and the result may not be minimal. Returns `None` if not
+// even deleting everything frees enough, and an error if `max_choices` is
+// above `MAX_CHOICES`.
+pub fn minimal_set(
+    tree: &Tree<FileSystemNode>,
+    sizes: &HashMap<NodeId, usize>,
+    needed: usize,
+    max_choices: usize,
+) -> Result<Option<Cleanup>, String> {
+    if max_choices > MAX_CHOICES {
+        return Err(format!(
+            "At most {} choices can be tracked, not {}",
+            MAX_CHOICES, max_choices
+        ));
+    }
+    let Some(root) = tree.roots().next() else {
+        return Ok(None);
+    };
+    let mut minimal = true;
+    let mut picks = vec![Pick::Nothing];
+
+    // For each directory, the totals reachable by deleting directories in it
+    // with the index of the pick reaching them
+    let mut choices: HashMap<NodeId, Vec<(usize, usize)>> = HashMap::new();
+    for id in tree
+        .post_order(root)
+        .filter(|id| is_node_dir(tree.get(*id)))
+    {
+        let mut combined = vec![(0, 0)];
+        for child in tree.children(id) {
+            let Some(child_choices) = choices.remove(child) else {
+                continue;
+            };
+
+            let mut next = Vec::with_capacity(combined.len() * child_choices.len());
+            for (size, pick) in &combined {
+                for (child_size, child_pick) in &child_choices {
+                    next.push((size + child_size, (*pick, *child_pick)));
+                }
+            }
+            let mut next = prune(next, needed);
+            if next.len() > max_choices {
+                next = thin(next, needed, max_choices);
+                minimal = false;
+            }
+
+            combined = next
+                .into_iter()
+                .map(|(size, pair)| match pair {
+                    (0, pick) | (pick, 0) => (size, pick),
+                    (a, b) => {
+                        picks.push(Pick::Both(a, b));
+                        (size, picks.len() - 1)
+                    }
+                })
+                .collect();
+        }
+
+        // Deleting the directory itself replaces all choices inside it
+        picks.push(Pick::Dir(id));
+        combined.push((sizes[&id], picks.len() - 1));
+        choices.insert(id, prune(combined, needed));
+    }
+
+    let Some((size, pick)) = choices
+        .remove(&root)
+        .and_then(|totals| totals.into_iter().find(|(size, _)| *size >= needed))
+    else {
+        return Ok(None);
+    };
+
+    let mut dirs = vec![];
+    let mut stack = vec![pick];
+    while let Some(pick) = stack.pop() {
+        match picks[pick] {
+            Pick::Nothing => (),
+            Pick::Dir(dir) => dirs.push(dir),
+            Pick::Both(a, b) => stack.extend([a, b]),
+        }
+    }
+
+    // Nothing frees less than exactly what is needed
+    minimal |= size == needed;
+
+    // Thinning may have dropped single directories freeing less
+    match smallest_dir(tree, sizes, needed) {
+        Some(dir) if !minimal && sizes[&dir] < size => Ok(Some(Cleanup {
+            dirs: vec![dir],
+            minimal,
+        })),
+        _ => Ok(Some(Cleanup { dirs, minimal })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dir_sizes, load_str, path_of, root};
+
+    // / (100)
+    //   a (60): b (25), c (30), 5 bytes of files
+    //   d (40): e (20), 20 bytes of files
+    fn example() -> Tree<FileSystemNode> {
+        load_str(
+            "$ cd /a/b\n$ ls\n25 f\n$ cd /a/c\n$ ls\n30 f\n$ cd /a\n$ ls\n5 g\n\
+             $ cd /d/e\n$ ls\n20 f\n$ cd /d\n$ ls\n20 g",
+        )
+    }
+
+    fn paths(tree: &Tree<FileSystemNode>, dirs: &[NodeId]) -> Vec<String> {
+        let mut paths: Vec<String> = dirs.iter().map(|d| path_of(tree, *d)).collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn test_space_needed() {
+        let disk = Disk {
+            total: 100,
+            required: 30,
+        };
+        assert_eq!(disk.space_needed(90), 20);
+        assert_eq!(disk.space_needed(70), 0);
+        assert_eq!(disk.space_needed(10), 0);
+        assert_eq!(disk.space_needed(120), 30);
+    }
+
+    #[test]
+    fn test_smallest_dir() {
+        let tree = example();
+        let sizes = dir_sizes(&tree);
+        let smallest = |needed| smallest_dir(&tree, &sizes, needed).map(|d| path_of(&tree, d));
+
+        assert_eq!(smallest(21), Some(String::from("/a/b")));
+        assert_eq!(smallest(30), Some(String::from("/a/c")));
+        assert_eq!(smallest(41), Some(String::from("/a")));
+        assert_eq!(smallest(100), Some(String::from("/")));
+        assert_eq!(smallest(101), None);
+    }
+
+    #[test]
+    fn test_minimal_set() {
+        let tree = example();
+        let sizes = dir_sizes(&tree);
+        let minimal = |needed| {
+            let cleanup = minimal_set(&tree, &sizes, needed, 1000).unwrap()?;
+            assert!(cleanup.minimal);
+            Some(paths(&tree, &cleanup.dirs))
+        };
+
+        // /a/b and /d/e free 45, less than /a or /d with /a/b
+        assert_eq!(minimal(45), Some(vec!["/a/b".into(), "/d/e".into()]));
+        assert_eq!(minimal(50), Some(vec!["/a/c".into(), "/d/e".into()]));
+        assert_eq!(minimal(30), Some(vec!["/a/c".into()]));
+        let cleanup = minimal_set(&tree, &sizes, 95, 1000).unwrap().unwrap();
+        assert_eq!(cleanup.dirs.iter().map(|d| sizes[d]).sum::<usize>(), 95);
+        assert_eq!(minimal(0), Some(vec![]));
+        assert_eq!(minimal(101), None);
+    }
+
+    #[test]
+    fn test_minimal_set_with_few_choices() {
+        let tree = example();
+        let sizes = dir_sizes(&tree);
+
+        for needed in 1..=100 {
+            let exact = minimal_set(&tree, &sizes, needed, 1000).unwrap().unwrap();
+            let total = |dirs: &[NodeId]| dirs.iter().map(|d| sizes[d]).sum::<usize>();
+
+            let cleanup = minimal_set(&tree, &sizes, needed, 1).unwrap().unwrap();
+            assert!(total(&cleanup.dirs) >= total(&exact.dirs));
+            assert!(total(&cleanup.dirs) >= needed);
+            // Never worse than deleting a single directory
+            let single = smallest_dir(&tree, &sizes, needed).unwrap();
+            assert!(total(&cleanup.dirs) <= sizes[&single]);
+        }
+    }
+
+    #[test]
+    fn test_minimal_set_on_wide_tree() {
+        // Two directories holding 16 directories each, whose subsets reach far
+        // more totals than can be tracked
+        let mut transcript = String::new();
+        for (side, offset) in [("a", 1000), ("b", 2000)] {
+            for i in 0..16 {
+                transcript.push_str(&format!(
+                    "$ cd /{}/d{}\n$ ls\n{} f\n",
+                    side,
+                    i,
+                    offset + (1 << i)
+                ));
+            }
+        }
+        let tree = load_str(&transcript);
+        let sizes = dir_sizes(&tree);
+        let needed = sizes[&root(&tree).unwrap()] / 3;
+
+        let cleanup = minimal_set(&tree, &sizes, needed, MAX_CHOICES)
+            .unwrap()
+            .unwrap();
+        let total: usize = cleanup.dirs.iter().map(|d| sizes[d]).sum();
+        assert!(total >= needed);
+        assert!(total <= sizes[&smallest_dir(&tree, &sizes, needed).unwrap()]);
+
+        assert!(minimal_set(&tree, &sizes, needed, MAX_CHOICES + 1).is_err());
+        assert!(minimal_set(&tree, &sizes, needed, usize::MAX).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File},
+    io,
+    path::Path,
+};
+
+use aoc22::tree::{NodeId, Tree};
+
+use crate::{invalid_data, FileSystemNode};
+
+fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+// The subtree of `id` as nested JSON objects, e.g.
+// `{"name":"/","type":"dir","size":584,"children":[{"name":"i","type":"file","size":584}]}`
+pub fn to_json(tree: &Tree<FileSystemNode>, sizes: &HashMap<NodeId, usize>, id: NodeId) -> String {
+    tree.fold(id, |id, node, children: Vec<String>| match node {
+        FileSystemNode::Dir(name) => format!(
+            "{{\"name\":{},\"type\":\"dir\",\"size\":{},\"children\":[{}]}}",
+            json_string(name),
+            sizes[&id],
+            children.join(",")
+        ),
+        FileSystemNode::File(name, size) => format!(
+            "{{\"name\":{},\"type\":\"file\",\"size\":{}}}",
+            json_string(name),
+            size
+        ),
+    })
+}
+
+// Creates the subtree of `id` inside the existing directory `dir`. Files are
+// created with their recorded size without writing any data, so they are sparse
+// on file systems supporting it.
+pub fn materialise(tree: &Tree<FileSystemNode>, id: NodeId, dir: &Path) -> io::Result<()> {
+    for child in tree.children(id) {
+        let node = tree.get(*child);
+        // Names come from the transcript and must not lead out of `dir`
+        if matches!(node.name(), "" | "." | "..") || node.name().contains('/') {
+            return Err(invalid_data(format!(
+                "Cannot create '{}' in {}",
+                node.name(),
+                dir.display()
+            )));
+        }
+
+        let path = dir.join(node.name());
+        match node {
+            FileSystemNode::Dir(_) => {
+                fs::create_dir(&path)?;
+                materialise(tree, *child, &path)?;
+            }
+            FileSystemNode::File(_, size) => File::create(&path)?.set_len(*size as u64)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::{
+        dir_sizes, find_dir_size, is_node_dir, load_str, path_of, resolve, TempDir, LINES,
+    };
+
+    #[test]
+    fn test_to_json() {
+        let tree = load_str("$ cd /\n$ ls\ndir a\n10 f\n$ cd a\n$ ls\n5 \"g\\h\"");
+        let sizes = dir_sizes(&tree);
+        assert_eq!(
+            to_json(&tree, &sizes, resolve(&tree, "/").unwrap()),
+            r#"{"name":"/","type":"dir","size":15,"children":[{"name":"a","type":"dir","size":5,"children":[{"name":"\"g\\h\"","type":"file","size":5}]},{"name":"f","type":"file","size":10}]}"#
+        );
+        assert_eq!(
+            to_json(&tree, &sizes, resolve(&tree, "/f").unwrap()),
+            r#"{"name":"f","type":"file","size":10}"#
+        );
+        assert_eq!(json_string("a\u{1}"), r#""a\u0001""#);
+    }
+
+    // Apparent size of everything below `path` as reported by `du`, or `None`
+    // when there is no `du` supporting `-b`, like on systems without GNU du
+    fn du(path: &Path) -> Option<usize> {
+        let output = Command::new("du")
+            .args(["-s", "-b"])
+            .arg(path)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout)
+            .ok()?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    }
+
+    #[test]
+    fn test_materialise_matches_du() {
+        let tree = load_str(LINES);
+        let sizes = dir_sizes(&tree);
+        let dir = TempDir::new("materialise");
+        materialise(&tree, resolve(&tree, "/").unwrap(), &dir.0).unwrap();
+
+        if du(&dir.0).is_none() {
+            eprintln!("Skipping test_materialise_matches_du: `du -s -b` is not available");
+            return;
+        }
+
+        for id in tree.ids().filter(|id| is_node_dir(tree.get(*id))) {
+            let path = dir.0.join(path_of(&tree, id).trim_start_matches('/'));
+            let du = du(&path).unwrap();
+
+            // `du` also counts the size of the directories themselves
+            let dirs_size: usize = std::iter::once(id)
+                .chain(tree.descendants(id))
+                .filter(|d| is_node_dir(tree.get(*d)))
+                .map(|d| {
+                    let path = dir.0.join(path_of(&tree, d).trim_start_matches('/'));
+                    fs::metadata(path).unwrap().len() as usize
+                })
+                .sum();
+            assert_eq!(du - dirs_size, find_dir_size(&tree, id));
+            assert_eq!(du - dirs_size, sizes[&id]);
+        }
+    }
+
+    #[test]
+    fn test_materialise_rejects_unsafe_names() {
+        let tree = load_str("$ cd /\n$ ls\ndir ..\n$ cd /\n$ ls");
+        let dir = TempDir::new("unsafe");
+
+        let result = materialise(&tree, resolve(&tree, "/").unwrap(), &dir.0);
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
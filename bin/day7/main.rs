@@ -1,223 +1,255 @@
 // Day 7
 
+mod cleanup;
+mod export;
+mod query;
+mod scan;
+
 use std::{
+    collections::HashMap,
     fs::File,
     io::{self, BufRead, BufReader},
     path::Path,
 };
 
+use aoc22::tree::{NodeId, Tree};
+
 #[derive(Debug)]
-struct Node<T>
-where
-    T: PartialEq,
-{
-    idx: usize,
-    val: T,
-    parent: Option<usize>,
-    children: Vec<usize>,
+enum FileSystemNode {
+    Dir(String),
+    File(String, usize),
 }
 
-impl<T> Node<T>
-where
-    T: PartialEq,
-{
-    fn new(idx: usize, val: T) -> Self {
-        Self {
-            idx,
-            val,
-            parent: None,
-            children: vec![],
+impl FileSystemNode {
+    fn name(&self) -> &str {
+        match self {
+            FileSystemNode::Dir(name) => name,
+            FileSystemNode::File(name, _) => name,
         }
     }
 }
 
-#[derive(Debug)]
-struct Tree<T>
-where
-    T: PartialEq,
-{
-    store: Vec<Node<T>>,
-}
-
-impl<T> Tree<T>
-where
-    T: PartialEq,
-{
-    fn new() -> Self {
-        Self { store: vec![] }
+impl PartialEq for FileSystemNode {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (FileSystemNode::Dir(name0), FileSystemNode::Dir(name1)) => name0 == name1,
+            (FileSystemNode::Dir(_), FileSystemNode::File(_, _)) => false,
+            (FileSystemNode::File(_, _), FileSystemNode::Dir(_)) => false,
+            (FileSystemNode::File(name0, size0), FileSystemNode::File(name1, size1)) => {
+                name0 == name1 && size0 == size1
+            }
+        }
     }
+}
 
-    fn empty(&self) -> bool {
-        self.store.is_empty()
+fn ensure_root(tree: &mut Tree<FileSystemNode>) -> NodeId {
+    match root(tree) {
+        Some(root) => root,
+        None => tree.add(FileSystemNode::Dir(String::from("/"))),
     }
+}
 
-    fn node(&mut self, val: T) -> usize {
-        self.store.push(Node::new(self.store.len(), val));
-        self.store.len() - 1
-    }
+// Follows `target` from `current` like `cd` does, where `target` is absolute
+// (`/a/e`) or relative (`a/e`, `../d`). Directories that have not been listed
+// yet are created on the way.
+fn change_dir(
+    tree: &mut Tree<FileSystemNode>,
+    current: NodeId,
+    target: &str,
+) -> Result<NodeId, String> {
+    let mut id = if target.starts_with('/') {
+        ensure_root(tree)
+    } else {
+        current
+    };
+
+    for name in target.split('/').filter(|c| !c.is_empty() && *c != ".") {
+        if name == ".." {
+            id = tree
+                .parent(id)
+                .ok_or_else(|| format!("Cannot cd '{}': already at /", target))?;
+            continue;
+        }
 
-    fn add_child(&mut self, parent: usize, child: usize) {
-        self.store[parent].children.push(child);
-        self.store[child].parent = Some(parent);
+        id = match child_with_name(tree, id, name) {
+            Some(child) if is_node_dir(tree.get(child)) => child,
+            Some(file) => {
+                return Err(format!(
+                    "Cannot cd '{}': {} is a file",
+                    target,
+                    path_of(tree, file)
+                ))
+            }
+            None => {
+                let child = tree.add(FileSystemNode::Dir(name.to_string()));
+                tree.add_child(id, child);
+                child
+            }
+        };
     }
 
-    fn validate(&self) -> bool {
-        self.store.iter().all(|n| {
-            n.children.iter().all(|c| {
-                self.store
-                    .get(*c)
-                    .map_or(false, |child| child.parent == Some(n.idx))
-            })
-        })
-    }
+    Ok(id)
 }
 
-#[derive(Debug)]
-enum FileSystemNode {
-    Dir(String),
-    File(String, usize),
+fn describe(node: &FileSystemNode) -> String {
+    match node {
+        FileSystemNode::Dir(_) => String::from("a directory"),
+        FileSystemNode::File(_, size) => format!("a file of size {}", size),
+    }
 }
 
-impl PartialEq for FileSystemNode {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (FileSystemNode::Dir(name0), FileSystemNode::Dir(name1)) => name0 == name1,
-            (FileSystemNode::Dir(_), FileSystemNode::File(_, _)) => false,
-            (FileSystemNode::File(_, _), FileSystemNode::Dir(_)) => false,
-            (FileSystemNode::File(name0, size0), FileSystemNode::File(name1, size1)) => {
-                name0 == name1 && size0 == size1
-            }
+// Adds an entry of `ls` output to the directory. Entries listed before are
+// skipped, so listing a directory again does not count its contents twice.
+fn add_entry(
+    tree: &mut Tree<FileSystemNode>,
+    dir: NodeId,
+    entry: FileSystemNode,
+) -> Result<(), String> {
+    match child_with_name(tree, dir, entry.name()) {
+        Some(existing) if *tree.get(existing) == entry => Ok(()),
+        Some(existing) => Err(format!(
+            "{} is listed as {}, but was {} before",
+            path_of(tree, existing),
+            describe(&entry),
+            describe(tree.get(existing))
+        )),
+        None => {
+            let child = tree.add(entry);
+            tree.add_child(dir, child);
+            Ok(())
         }
     }
 }
 
-fn load(tree: &mut Tree<FileSystemNode>, lines: &Vec<String>) {
-    let mut current: usize = usize::default();
-    for line in lines {
+// Loads a transcript into the tree, which always has a root afterwards even
+// when the transcript is empty
+fn load(tree: &mut Tree<FileSystemNode>, lines: &[String]) -> Result<(), String> {
+    // Commands before the first `cd` run in the root directory
+    let mut cwd = ensure_root(tree);
+    for (i, line) in lines.iter().enumerate() {
         let tokens: Vec<&str> = line.split_whitespace().collect();
-        match (tokens.get(0), tokens.get(1), tokens.get(2)) {
-            (Some(&"$"), Some(&"cd"), Some(&"..")) => {
-                let parent = tree.store[current].parent;
-                current = parent.unwrap();
+
+        match tokens.as_slice() {
+            [] => (),
+            ["$", "cd", target] => {
+                cwd =
+                    change_dir(tree, cwd, target).map_err(|e| format!("Line {}: {}", i + 1, e))?;
             }
-            (Some(&"$"), Some(&"cd"), Some(d)) => {
-                if tree.empty() {
-                    current = tree.node(FileSystemNode::Dir(d.to_string())); // If no nodes have been added to the tree, it will become root
-                } else {
-                    let children = tree.store[current].children.clone();
-                    current = *children
-                        .iter()
-                        .find(|&i| match &tree.store[*i].val {
-                            FileSystemNode::Dir(name) => d == name,
-                            _ => false,
-                        })
-                        .unwrap();
-                }
+            ["$", "ls"] => (), // We actually don't care about "ls"
+            ["$", command, ..] => {
+                return Err(format!("Line {}: Unknown command '{}'", i + 1, command))
             }
-            (Some(&"$"), Some(&"ls"), _) => (), // We actually don't care about "ls"
-            (Some(&"dir"), Some(name), _) => {
-                let child = tree.node(FileSystemNode::Dir(name.to_string()));
-                tree.add_child(current, child);
+            ["dir", name] => {
+                let entry = FileSystemNode::Dir(name.to_string());
+                add_entry(tree, cwd, entry).map_err(|e| format!("Line {}: {}", i + 1, e))?;
             }
-            (Some(size), Some(name), _) => {
-                let child = tree.node(FileSystemNode::File(
-                    name.to_string(),
-                    size.parse::<usize>().unwrap(),
-                ));
-                tree.add_child(current, child);
+            [size, name] => {
+                let size = size
+                    .parse::<usize>()
+                    .map_err(|e| format!("Line {}: Invalid size '{}': {}", i + 1, size, e))?;
+                let entry = FileSystemNode::File(name.to_string(), size);
+                add_entry(tree, cwd, entry).map_err(|e| format!("Line {}: {}", i + 1, e))?;
             }
-            (_, _, _) => todo!(),
+            _ => return Err(format!("Line {}: Unexpected output '{}'", i + 1, line)),
         }
     }
     assert!(tree.validate());
+    Ok(())
 }
 
-fn is_node_dir(node: &Node<FileSystemNode>) -> bool {
-    match node.val {
+fn is_node_dir(node: &FileSystemNode) -> bool {
+    match node {
         FileSystemNode::Dir(_) => true,
         FileSystemNode::File(_, _) => false,
     }
 }
 
-fn find_dir_size(tree: &Tree<FileSystemNode>, idx: usize) -> usize {
-    let node = &tree.store[idx];
-    match node.val {
-        FileSystemNode::Dir(_) => node
-            .children
-            .iter()
-            .map(|c| find_dir_size(tree, *c))
-            .reduce(|acc, s| acc + s)
-            .unwrap(),
-        FileSystemNode::File(_, size) => size,
-    }
-}
-
-fn find_node_with_name<'a>(
-    tree: &'a Tree<FileSystemNode>,
-    name: &str,
-) -> Option<&'a Node<FileSystemNode>> {
-    tree.store.iter().find(|n| match &n.val {
-        FileSystemNode::Dir(dir_name) => dir_name == name,
-        FileSystemNode::File(file_name, _) => file_name == name,
+fn find_dir_size(tree: &Tree<FileSystemNode>, id: NodeId) -> usize {
+    tree.fold(id, |_, node, children: Vec<usize>| match node {
+        FileSystemNode::Dir(_) => children.iter().sum(),
+        FileSystemNode::File(_, size) => *size,
     })
 }
 
-fn main() -> io::Result<()> {
-    let path = Path::new("./bin/day7/input");
-    let file = File::open(path)?;
-    let lines: Vec<String> = BufReader::new(file)
-        .lines()
-        .filter_map(|r| r.ok())
-        .collect();
+// Sizes of all nodes, computed in a single post-order pass so each directory
+// sums the already known sizes of its children
+fn dir_sizes(tree: &Tree<FileSystemNode>) -> HashMap<NodeId, usize> {
+    let mut sizes = HashMap::new();
+    for root in tree.roots() {
+        for id in tree.post_order(root) {
+            let size = match tree.get(id) {
+                FileSystemNode::Dir(_) => tree.children(id).iter().map(|c| sizes[c]).sum(),
+                FileSystemNode::File(_, size) => *size,
+            };
+            sizes.insert(id, size);
+        }
+    }
+    sizes
+}
 
-    let mut tree: Tree<FileSystemNode> = Tree::new();
-    load(&mut tree, &lines);
+fn root(tree: &Tree<FileSystemNode>) -> Option<NodeId> {
+    tree.roots().next()
+}
 
-    println!("Part 1");
-    let dirs_total_size = tree
-        .store
+fn child_with_name(tree: &Tree<FileSystemNode>, id: NodeId, name: &str) -> Option<NodeId> {
+    tree.children(id)
         .iter()
-        .filter(|n| is_node_dir(n))
-        .map(|n| find_dir_size(&tree, n.idx))
-        .filter(|size| *size < 100000)
-        .reduce(|acc, e| acc + e)
-        .unwrap();
-    println!("Total dir size for dirs < 100000: {:?}", dirs_total_size);
-
-    let total_disk_size = 70_000_000;
-    let space_required_update = 30_000_000;
+        .copied()
+        .find(|c| tree.get(*c).name() == name)
+}
 
-    println!("Part 2");
-    println!("Total disk size: {}", total_disk_size);
-    println!("Space required for update: {}", space_required_update);
-    let root = find_node_with_name(&tree, "/").unwrap();
-    let root_size = find_dir_size(&tree, root.idx);
-    println!("Root dir size: {}", root_size);
-    let space_needed =
-        (total_disk_size as i32 - space_required_update as i32 - root_size as i32).abs();
-    println!("Space needed: {}", space_needed);
+// Absolute path of a node, e.g. `/a/e`, built by following the parents up to the root
+fn path_of(tree: &Tree<FileSystemNode>, id: NodeId) -> String {
+    let mut names: Vec<&str> = std::iter::once(id)
+        .chain(tree.ancestors(id))
+        .filter(|n| tree.parent(*n).is_some())
+        .map(|n| tree.get(n).name())
+        .collect();
 
-    let dir_to_be_deleted = tree
-        .store
-        .iter()
-        .filter(|n| is_node_dir(n))
-        .map(|n| find_dir_size(&tree, n.idx))
-        .filter(|s| *s > space_needed as usize)
-        .reduce(|acc, e| acc.min(e))
-        .unwrap();
+    names.reverse();
+    format!("/{}", names.join("/"))
+}
 
-    println!("Size of dir to be deleted: {}", dir_to_be_deleted);
+// Finds the node at an absolute path such as `/a/e`
+fn resolve(tree: &Tree<FileSystemNode>, path: &str) -> Option<NodeId> {
+    let components = path.strip_prefix('/')?.split('/').filter(|c| !c.is_empty());
 
-    Ok(())
+    let mut id = root(tree)?;
+    for name in components {
+        id = child_with_name(tree, id, name)?;
+    }
+    Some(id)
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{cell::RefCell, rc::Rc};
+// Options that take no value
+const FLAGS: [&str; 3] = ["--minimal-set", "--json", "--transcript"];
+
+// Splits `--name value` options and flags from the words of a query
+fn split_options(args: &[String]) -> (HashMap<&str, &str>, Vec<&str>) {
+    let mut options = HashMap::new();
+    let mut query = vec![];
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        match arg {
+            _ if FLAGS.contains(&arg) => {
+                options.insert(arg, "");
+            }
+            _ if arg.starts_with("--") => {
+                options.insert(arg, args.next().unwrap_or_default());
+            }
+            _ => query.push(arg),
+        }
+    }
+    (options, query)
+}
 
-    use super::*;
+fn invalid_data(e: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
 
-    const LINES: &str = r#"$ cd /
+// The example transcript of the puzzle, shared by the tests of all modules
+#[cfg(test)]
+const LINES: &str = "$ cd /
 $ ls
 dir a
 14848514 b.txt
@@ -239,26 +271,356 @@ $ ls
 4060174 j
 8033020 d.log
 5626152 d.ext
-7214296 k"#;
+7214296 k";
+
+#[cfg(test)]
+fn try_load_str(input: &str) -> Result<Tree<FileSystemNode>, String> {
+    let lines: Vec<String> = input.lines().map(String::from).collect();
+    let mut tree: Tree<FileSystemNode> = Tree::new();
+    load(&mut tree, &lines)?;
+    Ok(tree)
+}
+
+#[cfg(test)]
+fn load_str(input: &str) -> Tree<FileSystemNode> {
+    try_load_str(input).unwrap()
+}
+
+// A directory for a test, removed with its contents when dropped even if the
+// test panics
+#[cfg(test)]
+struct TempDir(std::path::PathBuf);
+
+#[cfg(test)]
+impl TempDir {
+    fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("aoc22-day7-{}-{}", name, std::process::id()));
+        std::fs::create_dir(&path).unwrap();
+        TempDir(path)
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (options, query) = split_options(&args);
+
+    // `--scan DIR` analyses a real directory instead of the puzzle input
+    let tree = match options.get("--scan") {
+        Some(dir) => scan::scan(Path::new(dir))?,
+        None => {
+            let path = Path::new("./bin/day7/input");
+            let file = File::open(path)?;
+            // Lines that cannot be read are skipped rather than ending the input
+            #[allow(clippy::lines_filter_map_ok)]
+            let lines: Vec<String> = BufReader::new(file)
+                .lines()
+                .filter_map(|r| r.ok())
+                .collect();
+
+            let mut tree: Tree<FileSystemNode> = Tree::new();
+            load(&mut tree, &lines).map_err(invalid_data)?;
+            tree
+        }
+    };
+
+    if options.contains_key("--transcript") {
+        for line in scan::transcript(&tree, resolve(&tree, "/").unwrap()) {
+            println!("{}", line);
+        }
+        return Ok(());
+    }
+
+    if !query.is_empty() {
+        let query = query::Query::parse(&query).map_err(invalid_data)?;
+        print!("{}", query.run(&tree));
+        return Ok(());
+    }
+
+    if options.contains_key("--json") || options.contains_key("--materialise") {
+        let root = resolve(&tree, "/").unwrap();
+        if options.contains_key("--json") {
+            println!("{}", export::to_json(&tree, &dir_sizes(&tree), root));
+        }
+        if let Some(dir) = options.get("--materialise") {
+            export::materialise(&tree, root, Path::new(dir))?;
+        }
+        return Ok(());
+    }
+
+    let number = |name: &str, default: usize| -> io::Result<usize> {
+        match options.get(name) {
+            Some(value) => value
+                .parse::<usize>()
+                .map_err(|e| invalid_data(format!("Invalid {} '{}': {}", name, value, e))),
+            None => Ok(default),
+        }
+    };
+    let disk = cleanup::Disk {
+        total: number("--disk", 70_000_000)?,
+        required: number("--required", 30_000_000)?,
+    };
+
+    let max_choices = number("--max-choices", 1_000)?;
+    if max_choices > cleanup::MAX_CHOICES {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "--max-choices can be at most {}, not {}",
+                cleanup::MAX_CHOICES,
+                max_choices
+            ),
+        ));
+    }
+
+    let sizes = dir_sizes(&tree);
+    let dirs = || tree.ids().filter(|id| is_node_dir(tree.get(*id)));
+
+    println!("Part 1");
+    let dirs_total_size: usize = dirs()
+        .map(|d| sizes[&d])
+        .filter(|size| *size < 100000)
+        .sum();
+    println!("Total dir size for dirs < 100000: {:?}", dirs_total_size);
+
+    println!("Part 2");
+    println!("Total disk size: {}", disk.total);
+    println!("Space required for update: {}", disk.required);
+    let root = resolve(&tree, "/").unwrap();
+    let root_size = sizes[&root];
+    debug_assert_eq!(root_size, find_dir_size(&tree, root));
+    println!("Root dir size: {}", root_size);
+    let space_needed = disk.space_needed(root_size);
+    println!("Space needed: {}", space_needed);
+
+    if space_needed == 0 {
+        println!("Nothing to delete");
+        return Ok(());
+    }
+
+    match cleanup::smallest_dir(&tree, &sizes, space_needed) {
+        Some(dir) => {
+            println!("Dir to be deleted: {}", path_of(&tree, dir));
+            println!("Size of dir to be deleted: {}", sizes[&dir]);
+        }
+        None => println!("Deleting a single dir cannot free enough space"),
+    }
+
+    if options.contains_key("--minimal-set") {
+        if let Some(cleanup) =
+            cleanup::minimal_set(&tree, &sizes, space_needed, max_choices).map_err(invalid_data)?
+        {
+            let total: usize = cleanup.dirs.iter().map(|d| sizes[d]).sum();
+            let kind = if cleanup.minimal {
+                "Smallest"
+            } else {
+                "Best found"
+            };
+            println!("{} set of dirs to be deleted ({} in total):", kind, total);
+            let mut dirs: Vec<(String, usize)> = cleanup
+                .dirs
+                .iter()
+                .map(|d| (path_of(&tree, *d), sizes[d]))
+                .collect();
+            dirs.sort();
+            for (path, size) in dirs {
+                println!("  {} ({})", path, size);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use super::*;
 
     #[test]
     fn test_find_dir_size() {
-        let lines = LINES.lines().map(String::from).collect();
+        let lines: Vec<String> = LINES.lines().map(String::from).collect();
+
+        let mut tree: Tree<FileSystemNode> = Tree::new();
+        load(&mut tree, &lines).unwrap();
+
+        let node_e = resolve(&tree, "/a/e");
+        assert_eq!(find_dir_size(&tree, node_e.unwrap()), 584);
+
+        let node_a = resolve(&tree, "/a");
+        assert_eq!(find_dir_size(&tree, node_a.unwrap()), 94853);
+
+        let node_d = resolve(&tree, "/d");
+        assert_eq!(find_dir_size(&tree, node_d.unwrap()), 24933642);
+
+        let node_root = resolve(&tree, "/");
+        assert_eq!(find_dir_size(&tree, node_root.unwrap()), 48381165);
+    }
+
+    #[test]
+    fn test_dir_sizes() {
+        let lines: Vec<String> = LINES.lines().map(String::from).collect();
 
         let mut tree: Tree<FileSystemNode> = Tree::new();
-        load(&mut tree, &lines);
+        load(&mut tree, &lines).unwrap();
 
-        let node_e = find_node_with_name(&tree, "e");
-        assert_eq!(find_dir_size(&tree, node_e.unwrap().idx), 584);
+        let sizes = dir_sizes(&tree);
+        for id in tree.ids() {
+            assert_eq!(sizes[&id], find_dir_size(&tree, id));
+        }
 
-        let node_a = find_node_with_name(&tree, "a");
-        assert_eq!(find_dir_size(&tree, node_a.unwrap().idx), 94853);
+        assert_eq!(sizes[&resolve(&tree, "/a").unwrap()], 94853);
+        assert_eq!(sizes[&resolve(&tree, "/a/e/i").unwrap()], 584);
+    }
 
-        let node_d = find_node_with_name(&tree, "d");
-        assert_eq!(find_dir_size(&tree, node_d.unwrap().idx), 24933642);
+    #[test]
+    fn test_empty_dirs() {
+        // `b` is listed but empty, `c` is never visited
+        let tree = load_str("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\ndir b\ndir c\n100 f\n$ cd b\n$ ls");
+        let sizes = dir_sizes(&tree);
+
+        for (path, size) in [("/", 100), ("/a", 100), ("/a/b", 0), ("/a/c", 0)] {
+            let node = resolve(&tree, path).unwrap();
+            assert_eq!(find_dir_size(&tree, node), size);
+            assert_eq!(sizes[&node], size);
+        }
+    }
 
-        let node_root = find_node_with_name(&tree, "/");
-        assert_eq!(find_dir_size(&tree, node_root.unwrap().idx), 48381165);
+    #[test]
+    fn test_root_only() {
+        for input in ["$ cd /", "$ cd /\n$ ls"] {
+            let tree = load_str(input);
+            let root = resolve(&tree, "/").unwrap();
+            assert_eq!(find_dir_size(&tree, root), 0);
+            assert_eq!(dir_sizes(&tree), HashMap::from([(root, 0)]));
+        }
+    }
+
+    #[test]
+    fn test_empty_transcript() {
+        for input in ["", "\n\n"] {
+            let tree = load_str(input);
+            let root = resolve(&tree, "/").unwrap();
+            assert_eq!(tree.len(), 1);
+            assert_eq!(dir_sizes(&tree)[&root], 0);
+        }
+    }
+
+    #[test]
+    fn test_resolve_and_path_of() {
+        let tree = load_str(LINES);
+
+        for path in ["/", "/a", "/a/e", "/a/e/i", "/b.txt", "/d/d.log"] {
+            let id = resolve(&tree, path).unwrap();
+            assert_eq!(path_of(&tree, id), path);
+        }
+        assert_eq!(resolve(&tree, "/a/e/"), resolve(&tree, "/a/e"));
+        assert_eq!(resolve(&tree, "/e"), None);
+        assert_eq!(resolve(&tree, "/a/e/i/x"), None);
+        assert_eq!(resolve(&tree, "a"), None);
+    }
+
+    #[test]
+    fn test_same_names_in_different_dirs() {
+        let tree = load_str(
+            "$ cd /\n$ ls\ndir d\ndir x\n$ cd d\n$ ls\n10 f\n$ cd ..\n$ cd x\n$ ls\ndir d\n$ cd d\n$ ls\n20 f",
+        );
+        let sizes = dir_sizes(&tree);
+
+        assert_eq!(sizes[&resolve(&tree, "/d").unwrap()], 10);
+        assert_eq!(sizes[&resolve(&tree, "/x/d").unwrap()], 20);
+        assert_ne!(resolve(&tree, "/d/f"), resolve(&tree, "/x/d/f"));
+
+        for id in tree.ids() {
+            assert_eq!(resolve(&tree, &path_of(&tree, id)), Some(id));
+        }
+    }
+
+    #[test]
+    fn test_cd_root_mid_session() {
+        let tree = load_str("$ cd /\n$ cd a\n$ ls\n10 f\n$ cd /\n$ ls\n20 g\ndir a");
+        assert_eq!(dir_sizes(&tree)[&resolve(&tree, "/").unwrap()], 30);
+        assert_eq!(
+            resolve(&tree, "/g").map(|g| path_of(&tree, g)),
+            Some("/g".into())
+        );
+        // `dir a` refers to the directory created by `cd a`
+        assert_eq!(tree.children(resolve(&tree, "/").unwrap()).len(), 2);
+    }
+
+    #[test]
+    fn test_cd_multi_component_paths() {
+        let tree = load_str(
+            "$ cd /a/b\n$ ls\n1 x\n$ cd ../c/./d\n$ ls\n2 y\n$ cd ../../..\n$ cd a/b\n$ ls\n4 z",
+        );
+        let sizes = dir_sizes(&tree);
+        assert_eq!(sizes[&resolve(&tree, "/a/b").unwrap()], 5);
+        assert_eq!(sizes[&resolve(&tree, "/a/c/d").unwrap()], 2);
+        assert_eq!(sizes[&resolve(&tree, "/").unwrap()], 7);
+    }
+
+    #[test]
+    fn test_cd_into_unlisted_dir() {
+        let tree = load_str("$ cd x\n$ ls\n5 f");
+        assert_eq!(dir_sizes(&tree)[&resolve(&tree, "/x").unwrap()], 5);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        assert_eq!(
+            try_load_str("$ cd /\n$ cd ..").unwrap_err(),
+            "Line 2: Cannot cd '..': already at /"
+        );
+        assert_eq!(
+            try_load_str("$ cd /\n$ ls\n10 f\n$ cd f/g").unwrap_err(),
+            "Line 4: Cannot cd 'f/g': /f is a file"
+        );
+        assert_eq!(
+            try_load_str("$ cd /\n$ rm -rf a").unwrap_err(),
+            "Line 2: Unknown command 'rm'"
+        );
+        assert!(try_load_str("$ cd /\n$ ls\nbig f")
+            .unwrap_err()
+            .starts_with("Line 3: Invalid size 'big'"));
+        assert_eq!(
+            try_load_str("$ cd /\n$ ls\n1 2 3").unwrap_err(),
+            "Line 3: Unexpected output '1 2 3'"
+        );
+    }
+
+    #[test]
+    fn test_repeated_ls() {
+        let once = load_str(LINES);
+        let twice = load_str(&format!("{}\n$ cd /\n{}", LINES, LINES));
+
+        assert_eq!(twice.len(), once.len());
+        assert_eq!(dir_sizes(&twice)[&resolve(&twice, "/").unwrap()], 48381165);
+        // A partial listing after `cd` created the directory
+        let tree = load_str("$ cd /a\n$ cd ..\n$ ls\ndir a\n10 f\n$ ls\n10 f");
+        assert_eq!(tree.len(), 3);
+    }
+
+    #[test]
+    fn test_conflicting_ls() {
+        assert_eq!(
+            try_load_str("$ cd /\n$ ls\n10 f\n$ ls\n20 f").unwrap_err(),
+            "Line 5: /f is listed as a file of size 20, but was a file of size 10 before"
+        );
+        assert_eq!(
+            try_load_str("$ cd /\n$ ls\ndir f\n$ ls\n10 f").unwrap_err(),
+            "Line 5: /f is listed as a file of size 10, but was a directory before"
+        );
+        assert_eq!(
+            try_load_str("$ cd /\n$ cd a\n$ cd /\n$ ls\n10 a").unwrap_err(),
+            "Line 5: /a is listed as a file of size 10, but was a directory before"
+        );
     }
 
     #[derive(Debug)]
@@ -0,0 +1,252 @@
+use aoc22::tree::{NodeId, Tree};
+
+use crate::{dir_sizes, is_node_dir, path_of, root, FileSystemNode};
+
+// Commands to explore the reconstructed filesystem, given on the command line
+// after the binary name, e.g. `cargo run --bin day7 -- du -d 1`
+//
+//   tree                          every node with its size
+//   du -d <depth>                 directory sizes down to a depth, largest first
+//   find [-type f|d] [-size +N|-N] [-name <glob>]
+//                                 paths of the matching nodes
+//   largest <n>                   the n largest files
+#[derive(Debug, PartialEq)]
+pub enum Query {
+    Tree,
+    Du(usize),
+    Find(Filter),
+    Largest(usize),
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Filter {
+    dirs: Option<bool>,
+    larger_than: Option<usize>,
+    smaller_than: Option<usize>,
+    name: Option<String>,
+}
+
+fn parse_number(token: &str) -> Result<usize, String> {
+    token
+        .parse::<usize>()
+        .map_err(|e| format!("Invalid number '{}': {}", token, e))
+}
+
+fn parse_filter(args: &[&str]) -> Result<Filter, String> {
+    let mut filter = Filter::default();
+    for option in args.chunks(2) {
+        match option {
+            ["-type", "f"] => filter.dirs = Some(false),
+            ["-type", "d"] => filter.dirs = Some(true),
+            ["-size", size] => match (size.strip_prefix('+'), size.strip_prefix('-')) {
+                (Some(size), _) => filter.larger_than = Some(parse_number(size)?),
+                (_, Some(size)) => filter.smaller_than = Some(parse_number(size)?),
+                _ => {
+                    return Err(format!(
+                        "Expected '-size +N' or '-size -N', found '{}'",
+                        size
+                    ))
+                }
+            },
+            ["-name", glob] => filter.name = Some(glob.to_string()),
+            _ => return Err(format!("Invalid find option '{}'", option.join(" "))),
+        }
+    }
+    Ok(filter)
+}
+
+impl Query {
+    pub fn parse(args: &[&str]) -> Result<Self, String> {
+        match args {
+            ["tree"] => Ok(Query::Tree),
+            ["du", "-d", depth] => Ok(Query::Du(parse_number(depth)?)),
+            ["du"] => Ok(Query::Du(usize::MAX)),
+            ["find", options @ ..] => Ok(Query::Find(parse_filter(options)?)),
+            ["largest", n] => Ok(Query::Largest(parse_number(n)?)),
+            _ => Err(format!("Unknown query '{}'", args.join(" "))),
+        }
+    }
+
+    pub fn run(&self, tree: &Tree<FileSystemNode>) -> String {
+        let sizes = dir_sizes(tree);
+        let nodes = pre_order(tree);
+
+        let lines: Vec<String> = match self {
+            Query::Tree => nodes
+                .iter()
+                .map(|(id, depth)| {
+                    let node = tree.get(*id);
+                    let kind = if is_node_dir(node) { "dir" } else { "file" };
+                    format!(
+                        "{}- {} ({}, size={})",
+                        "  ".repeat(*depth),
+                        node.name(),
+                        kind,
+                        sizes[id]
+                    )
+                })
+                .collect(),
+            Query::Du(max_depth) => {
+                let mut dirs: Vec<(usize, String)> = nodes
+                    .iter()
+                    .filter(|(id, depth)| is_node_dir(tree.get(*id)) && depth <= max_depth)
+                    .map(|(id, _)| (sizes[id], path_of(tree, *id)))
+                    .collect();
+                dirs.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                dirs.iter()
+                    .map(|(size, path)| format!("{}\t{}", size, path))
+                    .collect()
+            }
+            Query::Find(filter) => nodes
+                .iter()
+                .filter(|(id, _)| filter.matches(tree.get(*id), sizes[id]))
+                .map(|(id, _)| path_of(tree, *id))
+                .collect(),
+            Query::Largest(n) => {
+                let mut files: Vec<(usize, String)> = nodes
+                    .iter()
+                    .filter_map(|(id, _)| match tree.get(*id) {
+                        FileSystemNode::File(_, size) => Some((*size, path_of(tree, *id))),
+                        FileSystemNode::Dir(_) => None,
+                    })
+                    .collect();
+                files.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+                files
+                    .iter()
+                    .take(*n)
+                    .map(|(size, path)| format!("{}\t{}", size, path))
+                    .collect()
+            }
+        };
+
+        lines.iter().map(|l| format!("{}\n", l)).collect()
+    }
+}
+
+impl Filter {
+    fn matches(&self, node: &FileSystemNode, size: usize) -> bool {
+        self.dirs.is_none_or(|dirs| dirs == is_node_dir(node))
+            && self.larger_than.is_none_or(|min| size > min)
+            && self.smaller_than.is_none_or(|max| size < max)
+            && self
+                .name
+                .as_ref()
+                .is_none_or(|glob| glob_matches(glob, node.name()))
+    }
+}
+
+// Nodes with their depth below the root, parents before their children
+fn pre_order(tree: &Tree<FileSystemNode>) -> Vec<(NodeId, usize)> {
+    let Some(root) = root(tree) else {
+        return vec![];
+    };
+    tree.pre_order(root)
+        .map(|id| (id, tree.depth(id)))
+        .collect()
+}
+
+// Shell style pattern where `*` matches any run of chars and `?` any single char
+fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // `matched[j]` tells whether the glob so far matches the first `j` chars of the name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for g in glob {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match g {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_str, LINES};
+
+    fn run(query: &str) -> String {
+        let args: Vec<&str> = query.split_whitespace().collect();
+        Query::parse(&args).unwrap().run(&load_str(LINES))
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_eq!(
+            run("tree"),
+            "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)
+"
+        );
+    }
+
+    #[test]
+    fn test_du() {
+        assert_eq!(run("du -d 0"), "48381165\t/\n");
+        assert_eq!(run("du -d 1"), "48381165\t/\n24933642\t/d\n94853\t/a\n");
+        assert_eq!(
+            run("du"),
+            "48381165\t/\n24933642\t/d\n94853\t/a\n584\t/a/e\n"
+        );
+    }
+
+    #[test]
+    fn test_find() {
+        assert_eq!(
+            run("find -size +8000000 -type f"),
+            "/b.txt\n/c.dat\n/d/d.log\n"
+        );
+        assert_eq!(run("find -type d -size -100000"), "/a\n/a/e\n");
+        assert_eq!(run("find -name d*"), "/d\n/d/d.log\n/d/d.ext\n");
+        assert_eq!(
+            run("find -name ?.*"),
+            "/a/h.lst\n/b.txt\n/c.dat\n/d/d.log\n/d/d.ext\n"
+        );
+        assert_eq!(run("find -name *.lst"), "/a/h.lst\n");
+        assert_eq!(run("find").lines().count(), 14);
+    }
+
+    #[test]
+    fn test_largest() {
+        assert_eq!(run("largest 2"), "14848514\t/b.txt\n8504156\t/c.dat\n");
+        assert_eq!(run("largest 100").lines().count(), 10);
+        assert_eq!(run("largest 0"), "");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Query::parse(&["du", "-d", "x"]).is_err());
+        assert!(Query::parse(&["find", "-size", "100"]).is_err());
+        assert!(Query::parse(&["find", "-type"]).is_err());
+        assert!(Query::parse(&["ls"]).is_err());
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("*", ""));
+        assert!(glob_matches("a*b?c", "axxbyc"));
+        assert!(glob_matches("*.*", "d.log"));
+        assert!(!glob_matches("*.*", "log"));
+        assert!(!glob_matches("?", ""));
+        assert!(!glob_matches("a", "ab"));
+    }
+}
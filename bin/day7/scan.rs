@@ -0,0 +1,115 @@
+use std::{fs, io, path::Path};
+
+use aoc22::tree::{NodeId, Tree};
+
+use crate::{invalid_data, FileSystemNode};
+
+// Builds the tree of a real directory, which becomes the root `/`. Entries are
+// added sorted by name and symbolic links are skipped.
+pub fn scan(dir: &Path) -> io::Result<Tree<FileSystemNode>> {
+    let mut tree = Tree::new();
+    let root = tree.add(FileSystemNode::Dir(String::from("/")));
+    scan_into(&mut tree, root, dir)?;
+    Ok(tree)
+}
+
+fn scan_into(tree: &mut Tree<FileSystemNode>, parent: NodeId, dir: &Path) -> io::Result<()> {
+    let mut entries = fs::read_dir(dir)?.collect::<io::Result<Vec<fs::DirEntry>>>()?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| invalid_data(format!("File name {:?} is not valid UTF-8", name)))?;
+        // A transcript separates names from sizes and commands by whitespace
+        if name.contains(char::is_whitespace) {
+            return Err(invalid_data(format!(
+                "Cannot write '{}' in {} to a transcript",
+                name,
+                dir.display()
+            )));
+        }
+
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let child = tree.add(FileSystemNode::Dir(name));
+            tree.add_child(parent, child);
+            scan_into(tree, child, &entry.path())?;
+        } else if file_type.is_file() {
+            let size = entry.metadata()?.len() as usize;
+            let child = tree.add(FileSystemNode::File(name, size));
+            tree.add_child(parent, child);
+        }
+    }
+    Ok(())
+}
+
+// The `$ cd` and `$ ls` commands with their output exploring the whole tree,
+// in the format `load` reads
+pub fn transcript(tree: &Tree<FileSystemNode>, root: NodeId) -> Vec<String> {
+    let mut lines = vec![String::from("$ cd /")];
+    list(tree, root, &mut lines);
+    lines
+}
+
+fn list(tree: &Tree<FileSystemNode>, dir: NodeId, lines: &mut Vec<String>) {
+    lines.push(String::from("$ ls"));
+    for child in tree.children(dir) {
+        lines.push(match tree.get(*child) {
+            FileSystemNode::Dir(name) => format!("dir {}", name),
+            FileSystemNode::File(name, size) => format!("{} {}", size, name),
+        });
+    }
+
+    for child in tree.children(dir) {
+        if let FileSystemNode::Dir(name) = tree.get(*child) {
+            lines.push(format!("$ cd {}", name));
+            list(tree, *child, lines);
+            lines.push(String::from("$ cd .."));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{dir_sizes, export, load_str, resolve, TempDir, LINES};
+
+    fn to_json(tree: &Tree<FileSystemNode>) -> String {
+        export::to_json(tree, &dir_sizes(tree), resolve(tree, "/").unwrap())
+    }
+
+    #[test]
+    fn test_transcript_round_trip() {
+        let tree = load_str(LINES);
+        let loaded = load_str(&transcript(&tree, resolve(&tree, "/").unwrap()).join("\n"));
+        assert_eq!(to_json(&loaded), to_json(&tree));
+    }
+
+    #[test]
+    fn test_scanned_transcript_round_trip() {
+        let dir = TempDir::new("scan");
+        fs::create_dir_all(dir.0.join("a/e")).unwrap();
+        fs::create_dir(dir.0.join("d")).unwrap();
+        fs::write(dir.0.join("b.txt"), "bbb").unwrap();
+        fs::write(dir.0.join("a/e/i"), "i").unwrap();
+        fs::write(dir.0.join("d/j"), "jj").unwrap();
+
+        let scanned = scan(&dir.0).unwrap();
+        let loaded = load_str(&transcript(&scanned, resolve(&scanned, "/").unwrap()).join("\n"));
+        assert_eq!(
+            to_json(&scanned),
+            r#"{"name":"/","type":"dir","size":6,"children":[{"name":"a","type":"dir","size":1,"children":[{"name":"e","type":"dir","size":1,"children":[{"name":"i","type":"file","size":1}]}]},{"name":"b.txt","type":"file","size":3},{"name":"d","type":"dir","size":2,"children":[{"name":"j","type":"file","size":2}]}]}"#
+        );
+        assert_eq!(to_json(&loaded), to_json(&scanned));
+    }
+
+    #[test]
+    fn test_scan_rejects_whitespace() {
+        let dir = TempDir::new("whitespace");
+        fs::write(dir.0.join("a b"), "").unwrap();
+
+        assert_eq!(scan(&dir.0).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
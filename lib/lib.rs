@@ -0,0 +1,3 @@
+// Building blocks shared by the days
+
+pub mod tree;
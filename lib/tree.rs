@@ -0,0 +1,374 @@
+use std::collections::{HashMap, VecDeque};
+
+// Index of a node in the arena of a `Tree`. Ids stay valid until their node is
+// removed and are never reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(usize);
+
+#[derive(Debug)]
+struct Node<T> {
+    val: T,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+// Arena of nodes linked to their parent and children by id. A tree can hold
+// several roots, i.e. nodes without a parent.
+#[derive(Debug)]
+pub struct Tree<T> {
+    // Removed nodes leave a `None` behind so the other ids stay valid
+    store: Vec<Option<Node<T>>>,
+}
+
+impl<T> Default for Tree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Tree<T> {
+    pub fn new() -> Self {
+        Self { store: vec![] }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.store.iter().flatten().count()
+    }
+
+    fn node(&self, id: NodeId) -> &Node<T> {
+        self.store[id.0].as_ref().expect("node was removed")
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node<T> {
+        self.store[id.0].as_mut().expect("node was removed")
+    }
+
+    // Adds a node without a parent
+    pub fn add(&mut self, val: T) -> NodeId {
+        self.store.push(Some(Node {
+            val,
+            parent: None,
+            children: vec![],
+        }));
+        NodeId(self.store.len() - 1)
+    }
+
+    // Attaches a node without a parent as the last child of `parent`
+    pub fn add_child(&mut self, parent: NodeId, child: NodeId) {
+        debug_assert!(self.parent(child).is_none());
+        self.node_mut(parent).children.push(child);
+        self.node_mut(child).parent = Some(parent);
+    }
+
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.store.get(id.0).is_some_and(Option::is_some)
+    }
+
+    pub fn get(&self, id: NodeId) -> &T {
+        &self.node(id).val
+    }
+
+    pub fn get_mut(&mut self, id: NodeId) -> &mut T {
+        &mut self.node_mut(id).val
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    // All nodes in the order they were added
+    pub fn ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.store
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| n.is_some())
+            .map(|(i, _)| NodeId(i))
+    }
+
+    pub fn roots(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.ids().filter(|id| self.parent(*id).is_none())
+    }
+
+    // Number of ancestors of the node, 0 for a root
+    pub fn depth(&self, id: NodeId) -> usize {
+        self.ancestors(id).count()
+    }
+
+    // The parent of the node, its parent and so on up to the root
+    pub fn ancestors(&self, id: NodeId) -> Ancestors<'_, T> {
+        Ancestors {
+            tree: self,
+            next: self.parent(id),
+        }
+    }
+
+    // The nodes below `id` in pre-order, without `id` itself
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        self.pre_order(id).skip(1)
+    }
+
+    // The subtree of `id`, each node before its children
+    pub fn pre_order(&self, id: NodeId) -> PreOrder<'_, T> {
+        PreOrder {
+            tree: self,
+            stack: vec![id],
+        }
+    }
+
+    // The subtree of `id`, each node after its children
+    pub fn post_order(&self, id: NodeId) -> PostOrder<'_, T> {
+        PostOrder {
+            tree: self,
+            stack: vec![(id, false)],
+        }
+    }
+
+    // The subtree of `id` level by level
+    pub fn breadth_first(&self, id: NodeId) -> BreadthFirst<'_, T> {
+        BreadthFirst {
+            tree: self,
+            queue: VecDeque::from([id]),
+        }
+    }
+
+    // Combines the values of the subtree of `id` bottom up, `f` gets each node
+    // with the results for its children
+    pub fn fold<A, F>(&self, id: NodeId, mut f: F) -> A
+    where
+        F: FnMut(NodeId, &T, Vec<A>) -> A,
+    {
+        let mut results: HashMap<NodeId, A> = HashMap::new();
+        for n in self.post_order(id) {
+            let children = self
+                .children(n)
+                .iter()
+                .map(|c| results.remove(c).expect("children are folded first"))
+                .collect();
+            results.insert(n, f(n, self.get(n), children));
+        }
+        results
+            .remove(&id)
+            .expect("the subtree root is folded last")
+    }
+
+    // Removes the node and its subtree, returning the node's value
+    pub fn remove(&mut self, id: NodeId) -> T {
+        if let Some(parent) = self.parent(id) {
+            self.node_mut(parent).children.retain(|c| *c != id);
+        }
+
+        let subtree: Vec<NodeId> = self.descendants(id).collect();
+        for n in subtree {
+            self.store[n.0] = None;
+        }
+        self.store[id.0].take().expect("node was removed").val
+    }
+
+    // Moves the node and its subtree to the end of the children of `parent`
+    pub fn reparent(&mut self, id: NodeId, parent: NodeId) -> Result<(), String> {
+        if id == parent || self.ancestors(parent).any(|a| a == id) {
+            return Err(format!(
+                "Cannot move node {} below itself ({})",
+                id.0, parent.0
+            ));
+        }
+
+        if let Some(old) = self.parent(id) {
+            self.node_mut(old).children.retain(|c| *c != id);
+        }
+        self.node_mut(id).parent = None;
+        self.add_child(parent, id);
+        Ok(())
+    }
+
+    pub fn validate(&self) -> bool {
+        self.ids().all(|id| {
+            self.children(id)
+                .iter()
+                .all(|c| self.contains(*c) && self.parent(*c) == Some(id))
+                && self.parent(id).is_none_or(|p| self.contains(p))
+        })
+    }
+}
+
+pub struct Ancestors<'a, T> {
+    tree: &'a Tree<T>,
+    next: Option<NodeId>,
+}
+
+impl<T> Iterator for Ancestors<'_, T> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.next?;
+        self.next = self.tree.parent(id);
+        Some(id)
+    }
+}
+
+pub struct PreOrder<'a, T> {
+    tree: &'a Tree<T>,
+    stack: Vec<NodeId>,
+}
+
+impl<T> Iterator for PreOrder<'_, T> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.stack.pop()?;
+        self.stack
+            .extend(self.tree.children(id).iter().rev().copied());
+        Some(id)
+    }
+}
+
+pub struct PostOrder<'a, T> {
+    tree: &'a Tree<T>,
+    // Nodes with whether their children have been visited already
+    stack: Vec<(NodeId, bool)>,
+}
+
+impl<T> Iterator for PostOrder<'_, T> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((id, children_visited)) = self.stack.pop() {
+            if children_visited {
+                return Some(id);
+            }
+            self.stack.push((id, true));
+            self.stack
+                .extend(self.tree.children(id).iter().rev().map(|c| (*c, false)));
+        }
+        None
+    }
+}
+
+pub struct BreadthFirst<'a, T> {
+    tree: &'a Tree<T>,
+    queue: VecDeque<NodeId>,
+}
+
+impl<T> Iterator for BreadthFirst<'_, T> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let id = self.queue.pop_front()?;
+        self.queue.extend(self.tree.children(id));
+        Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //        a
+    //      / | \
+    //     b  c  d
+    //    / \     \
+    //   e   f     g
+    fn example() -> (Tree<char>, HashMap<char, NodeId>) {
+        let mut tree = Tree::new();
+        let mut ids = HashMap::new();
+        for c in "abcdefg".chars() {
+            ids.insert(c, tree.add(c));
+        }
+        for (parent, child) in [
+            ('a', 'b'),
+            ('a', 'c'),
+            ('a', 'd'),
+            ('b', 'e'),
+            ('b', 'f'),
+            ('d', 'g'),
+        ] {
+            tree.add_child(ids[&parent], ids[&child]);
+        }
+        (tree, ids)
+    }
+
+    fn values(tree: &Tree<char>, ids: impl Iterator<Item = NodeId>) -> String {
+        ids.map(|id| *tree.get(id)).collect()
+    }
+
+    #[test]
+    fn test_traversals() {
+        let (tree, ids) = example();
+        let a = ids[&'a'];
+
+        assert_eq!(values(&tree, tree.pre_order(a)), "abefcdg");
+        assert_eq!(values(&tree, tree.post_order(a)), "efbcgda");
+        assert_eq!(values(&tree, tree.breadth_first(a)), "abcdefg");
+        assert_eq!(values(&tree, tree.descendants(ids[&'b'])), "ef");
+        assert_eq!(values(&tree, tree.pre_order(ids[&'g'])), "g");
+        assert_eq!(values(&tree, tree.roots()), "a");
+    }
+
+    #[test]
+    fn test_ancestors_and_depth() {
+        let (tree, ids) = example();
+
+        assert_eq!(values(&tree, tree.ancestors(ids[&'f'])), "ba");
+        assert_eq!(values(&tree, tree.ancestors(ids[&'a'])), "");
+        assert_eq!(tree.depth(ids[&'a']), 0);
+        assert_eq!(tree.depth(ids[&'d']), 1);
+        assert_eq!(tree.depth(ids[&'g']), 2);
+    }
+
+    #[test]
+    fn test_fold() {
+        let (tree, ids) = example();
+
+        let size = tree.fold(ids[&'a'], |_, _, children: Vec<usize>| {
+            1 + children.iter().sum::<usize>()
+        });
+        assert_eq!(size, 7);
+
+        let height = tree.fold(ids[&'a'], |_, _, children: Vec<usize>| {
+            children.into_iter().max().map_or(0, |h| h + 1)
+        });
+        assert_eq!(height, 2);
+
+        let rendered = tree.fold(ids[&'b'], |_, c, children: Vec<String>| {
+            format!("{}({})", c, children.join(","))
+        });
+        assert_eq!(rendered, "b(e(),f())");
+    }
+
+    #[test]
+    fn test_remove() {
+        let (mut tree, ids) = example();
+
+        assert_eq!(tree.remove(ids[&'b']), 'b');
+        assert_eq!(tree.len(), 4);
+        assert!(!tree.contains(ids[&'e']));
+        assert!(tree.contains(ids[&'g']));
+        assert_eq!(values(&tree, tree.pre_order(ids[&'a'])), "acdg");
+        assert!(tree.validate());
+
+        tree.remove(ids[&'a']);
+        assert!(tree.is_empty());
+    }
+
+    #[test]
+    fn test_reparent() {
+        let (mut tree, ids) = example();
+
+        tree.reparent(ids[&'b'], ids[&'g']).unwrap();
+        assert_eq!(values(&tree, tree.pre_order(ids[&'a'])), "acdgbef");
+        assert_eq!(tree.depth(ids[&'f']), 4);
+        assert!(tree.validate());
+
+        assert!(tree.reparent(ids[&'d'], ids[&'e']).is_err());
+        assert!(tree.reparent(ids[&'d'], ids[&'d']).is_err());
+        assert_eq!(values(&tree, tree.pre_order(ids[&'a'])), "acdgbef");
+    }
+}